@@ -0,0 +1,205 @@
+use alloc::vec::Vec;
+use core::{mem::size_of, ptr::read_unaligned, slice};
+use x86_64::PhysAddr;
+
+use crate::memory::phys_to_virt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    RsdpNotFound,
+    InvalidChecksum,
+    TableNotFound,
+}
+
+/// Root System Description Pointer. The fields after `rsdt_address` only
+/// exist from revision 2 and up
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+/// Header shared by all System Description Tables
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+/// Sum all bytes of a structure. Valid ACPI structures sum to 0
+unsafe fn checksum(address: PhysAddr, length: usize) -> u8 {
+    let bytes = slice::from_raw_parts(phys_to_virt(address).as_ptr::<u8>(), length);
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Scan `length` bytes from `start` for the RSDP signature. The RSDP is
+/// always 16 byte aligned
+unsafe fn scan_for_rsdp(start: u64, length: u64) -> Option<PhysAddr> {
+    (start..start + length)
+        .step_by(16)
+        .map(PhysAddr::new)
+        .find(|address| {
+            let signature = read_unaligned(phys_to_virt(*address).as_ptr::<[u8; 8]>());
+            &signature == b"RSD PTR " && checksum(*address, 20) == 0
+        })
+}
+
+/// Locate the RSDP in either the first KiB of the Extended BIOS Data Area, or
+/// the BIOS area between 0xE0000 and 0xFFFFF
+unsafe fn find_rsdp() -> Result<Rsdp, AcpiError> {
+    let ebda_segment = read_unaligned(phys_to_virt(PhysAddr::new(0x40e)).as_ptr::<u16>());
+    let ebda = (ebda_segment as u64) << 4;
+    let address = scan_for_rsdp(ebda, 1024)
+        .or_else(|| scan_for_rsdp(0xe0000, 0x20000))
+        .ok_or(AcpiError::RsdpNotFound)?;
+    Ok(read_unaligned(phys_to_virt(address).as_ptr::<Rsdp>()))
+}
+
+/// Read and validate the header of the table at `address`
+unsafe fn read_header(address: PhysAddr) -> Result<SdtHeader, AcpiError> {
+    let header = read_unaligned(phys_to_virt(address).as_ptr::<SdtHeader>());
+    if checksum(address, header.length as usize) != 0 {
+        return Err(AcpiError::InvalidChecksum);
+    }
+    Ok(header)
+}
+
+/// Find the physical address of the table with the given `signature`, by
+/// walking the entries of the XSDT (or RSDT on ACPI 1.0 systems)
+pub fn find_table(signature: &[u8; 4]) -> Result<PhysAddr, AcpiError> {
+    unsafe {
+        let rsdp = find_rsdp()?;
+        let (root, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_address != 0 {
+            (PhysAddr::new(rsdp.xsdt_address), size_of::<u64>())
+        } else {
+            (PhysAddr::new(rsdp.rsdt_address as u64), size_of::<u32>())
+        };
+        let header = read_header(root)?;
+        let entries = (header.length as usize - size_of::<SdtHeader>()) / entry_size;
+        let first_entry = root + size_of::<SdtHeader>();
+
+        for i in 0..entries {
+            let entry = phys_to_virt(first_entry + i * entry_size);
+            let table = if entry_size == size_of::<u64>() {
+                read_unaligned(entry.as_ptr::<u64>())
+            } else {
+                read_unaligned(entry.as_ptr::<u32>()) as u64
+            };
+            let table = PhysAddr::new(table);
+            let table_header = read_unaligned(phys_to_virt(table).as_ptr::<SdtHeader>());
+            if &table_header.signature == signature {
+                read_header(table)?;
+                return Ok(table);
+            }
+        }
+        Err(AcpiError::TableNotFound)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IoApicEntry {
+    pub id: u8,
+    pub address: PhysAddr,
+    pub gsi_base: u32,
+}
+
+/// Mapping of an ISA IRQ to a different global system interrupt, along with
+/// its polarity and trigger mode
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub flags: u16,
+}
+
+impl InterruptOverride {
+    pub fn active_low(&self) -> bool {
+        self.flags & 0b11 == 0b11
+    }
+
+    pub fn level_triggered(&self) -> bool {
+        (self.flags >> 2) & 0b11 == 0b11
+    }
+}
+
+/// The parts of the Multiple APIC Description Table we care about
+#[derive(Debug)]
+pub struct Madt {
+    pub local_apic_address: PhysAddr,
+    pub has_legacy_pics: bool,
+    pub processors: Vec<u8>,
+    pub io_apics: Vec<IoApicEntry>,
+    pub overrides: Vec<InterruptOverride>,
+}
+
+pub fn parse_madt() -> Result<Madt, AcpiError> {
+    let address = find_table(b"APIC")?;
+    unsafe {
+        let header = read_unaligned(phys_to_virt(address).as_ptr::<SdtHeader>());
+        let body = address + size_of::<SdtHeader>();
+        let local_apic_address = read_unaligned(phys_to_virt(body).as_ptr::<u32>());
+        let flags = read_unaligned(phys_to_virt(body + 4u64).as_ptr::<u32>());
+
+        let mut madt = Madt {
+            local_apic_address: PhysAddr::new(local_apic_address as u64),
+            has_legacy_pics: flags & 1 == 1,
+            processors: Vec::new(),
+            io_apics: Vec::new(),
+            overrides: Vec::new(),
+        };
+
+        let end = address + header.length as u64;
+        let mut entry = body + 8u64;
+        while entry < end {
+            let bytes = phys_to_virt(entry).as_ptr::<u8>();
+            let entry_type = *bytes;
+            let length = *bytes.add(1);
+            match entry_type {
+                // Processor local APIC. Only count the ones that are enabled
+                0 => {
+                    let flags = read_unaligned(bytes.add(4) as *const u32);
+                    if flags & 1 == 1 {
+                        madt.processors.push(*bytes.add(3));
+                    }
+                }
+                1 => madt.io_apics.push(IoApicEntry {
+                    id: *bytes.add(2),
+                    address: PhysAddr::new(read_unaligned(bytes.add(4) as *const u32) as u64),
+                    gsi_base: read_unaligned(bytes.add(8) as *const u32),
+                }),
+                2 => madt.overrides.push(InterruptOverride {
+                    irq: *bytes.add(3),
+                    gsi: read_unaligned(bytes.add(4) as *const u32),
+                    flags: read_unaligned(bytes.add(8) as *const u16),
+                }),
+                // 64 bit local APIC address override
+                5 => {
+                    madt.local_apic_address =
+                        PhysAddr::new(read_unaligned(bytes.add(4) as *const u64));
+                }
+                _ => {}
+            }
+            if length == 0 {
+                break;
+            }
+            entry += length as u64;
+        }
+        Ok(madt)
+    }
+}
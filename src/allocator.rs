@@ -4,6 +4,8 @@ pub mod bump;
 pub mod linked_list;
 pub mod sorted_linked_list;
 
+use alloc::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use spin::{Mutex, MutexGuard};
 use x86_64::{
     structures::paging::{
@@ -12,26 +14,71 @@ use x86_64::{
     VirtAddr,
 };
 
-use self::sorted_linked_list::SortedLinkedListAllocator;
+use self::{
+    bump::BumpAllocator, linked_list::LinkedListAllocator,
+    sorted_linked_list::SortedLinkedListAllocator,
+};
+use crate::boot_params;
+
+pub const HEAP_START: usize = 0x4444_4444_0000;
+/// Heap size unless the `heap` boot option says otherwise
+pub const DEFAULT_HEAP_SIZE: usize = 50 * 1024;
+
+/// Heap allocators the kernel can run on, picked with the `allocator` boot
+/// option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AllocatorKind {
+    Bump,
+    LinkedList,
+    SortedLinkedList,
+}
 
 #[global_allocator]
+static HEAP: Heap = Heap;
+
+/// The default allocator
 pub static ALLOCATOR: Locked<SortedLinkedListAllocator> =
     Locked::new(SortedLinkedListAllocator::new());
-// pub static ALLOCATOR: Locked<LinkedListAllocator> = Locked::new(LinkedListAllocator::new());
+static BUMP_ALLOCATOR: Locked<BumpAllocator> = Locked::new(BumpAllocator::new());
+static LINKED_LIST_ALLOCATOR: Locked<LinkedListAllocator> = Locked::new(LinkedListAllocator::new());
 
-//use self::bump::BumpAllocator;
-//pub static ALLOCATOR: Locked<BumpAllocator> = Locked::new(BumpAllocator::new());
+// Only changed by `init_heap`, before anything is allocated
+static SELECTED: AtomicU8 = AtomicU8::new(AllocatorKind::SortedLinkedList as u8);
+static HEAP_SIZE: AtomicUsize = AtomicUsize::new(0);
 
-pub const HEAP_START: usize = 0x4444_4444_0000;
-pub const HEAP_SIZE: usize = 50 * 1024;
+/// Hands allocations to whichever allocator was selected at boot
+struct Heap;
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match allocator_kind() {
+            AllocatorKind::Bump => BUMP_ALLOCATOR.alloc(layout),
+            AllocatorKind::LinkedList => LINKED_LIST_ALLOCATOR.alloc(layout),
+            AllocatorKind::SortedLinkedList => ALLOCATOR.alloc(layout),
+        }
+    }
 
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match allocator_kind() {
+            AllocatorKind::Bump => BUMP_ALLOCATOR.dealloc(ptr, layout),
+            AllocatorKind::LinkedList => LINKED_LIST_ALLOCATOR.dealloc(ptr, layout),
+            AllocatorKind::SortedLinkedList => ALLOCATOR.dealloc(ptr, layout),
+        }
+    }
+}
+
+/// Map the heap and hand it to the allocator, with the size and allocator
+/// given in the boot parameters
 pub fn init_heap(
     mapper: &mut impl Mapper<Size4KiB>,
     frame_allocator: &mut impl FrameAllocator<Size4KiB>,
 ) -> Result<(), MapToError<Size4KiB>> {
+    let params = boot_params::get();
+    let heap_size = params.heap_size;
     let page_range = {
         let start_add = VirtAddr::new(HEAP_START as u64);
-        let end_add = start_add + HEAP_SIZE - 1 as u64;
+        let end_add = start_add + heap_size - 1u64;
         let start_page = Page::containing_address(start_add);
         let end_page = Page::containing_address(end_add);
         Page::range_inclusive(start_page, end_page)
@@ -47,11 +94,38 @@ pub fn init_heap(
         }
     }
 
-    ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
+    match params.allocator {
+        AllocatorKind::Bump => unsafe { BUMP_ALLOCATOR.lock().init(HEAP_START, heap_size) },
+        AllocatorKind::LinkedList => LINKED_LIST_ALLOCATOR.lock().init(HEAP_START, heap_size),
+        AllocatorKind::SortedLinkedList => ALLOCATOR.lock().init(HEAP_START, heap_size),
+    }
+    SELECTED.store(params.allocator as u8, Ordering::SeqCst);
+    HEAP_SIZE.store(heap_size, Ordering::SeqCst);
 
     Ok(())
 }
 
+pub fn allocator_kind() -> AllocatorKind {
+    match SELECTED.load(Ordering::Relaxed) {
+        0 => AllocatorKind::Bump,
+        1 => AllocatorKind::LinkedList,
+        _ => AllocatorKind::SortedLinkedList,
+    }
+}
+
+/// Size of the heap in bytes, or 0 before `init_heap`
+pub fn heap_size() -> usize {
+    HEAP_SIZE.load(Ordering::Relaxed)
+}
+
+/// Free heap bytes, if the selected allocator keeps track of them
+pub fn free_space() -> Option<usize> {
+    match allocator_kind() {
+        AllocatorKind::SortedLinkedList => Some(ALLOCATOR.lock().get_free_space()),
+        _ => None,
+    }
+}
+
 pub fn align_up(addr: usize, align: usize) -> usize {
     // super smart piece of bit magic which is actually a lot faster:
     // (addr + align -1) & !(align-1)
@@ -0,0 +1,277 @@
+use core::{
+    arch::x86_64::__cpuid,
+    ptr::{read_volatile, write_volatile},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use alloc::vec::Vec;
+use conquer_once::spin::OnceCell;
+use spin::Mutex;
+use x86_64::{
+    instructions::port::Port,
+    structures::paging::{mapper::MapToError, FrameAllocator, Mapper, Size4KiB},
+    VirtAddr,
+};
+
+use crate::{
+    acpi::{self, AcpiError, InterruptOverride},
+    interrupts::{self, InterruptIndex},
+    memory, pit,
+};
+
+/// Vector the local APIC delivers spurious interrupts on. The low 4 bits have
+/// to be set on older processors
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+/// Most CPUs per-CPU state is kept for
+pub const MAX_CPUS: usize = 8;
+
+// Local APIC register offsets
+const LAPIC_ID: usize = 0x20;
+const LAPIC_TASK_PRIORITY: usize = 0x80;
+const LAPIC_EOI: usize = 0xb0;
+const LAPIC_SPURIOUS: usize = 0xf0;
+const LAPIC_LVT_TIMER: usize = 0x320;
+const LAPIC_TIMER_INITIAL: usize = 0x380;
+const LAPIC_TIMER_CURRENT: usize = 0x390;
+const LAPIC_TIMER_DIVIDE: usize = 0x3e0;
+
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+
+// IO APIC register offsets
+const IOAPIC_VERSION: u32 = 0x01;
+const IOAPIC_REDIRECTION_TABLE: u32 = 0x10;
+
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+const REDIRECTION_LEVEL_TRIGGERED: u64 = 1 << 15;
+const REDIRECTION_MASKED: u64 = 1 << 16;
+
+#[derive(Debug)]
+pub enum ApicError {
+    Unsupported,
+    Acpi(AcpiError),
+    NoIoApic,
+    MappingFailed(MapToError<Size4KiB>),
+}
+
+impl From<AcpiError> for ApicError {
+    fn from(err: AcpiError) -> Self {
+        ApicError::Acpi(err)
+    }
+}
+
+impl From<MapToError<Size4KiB>> for ApicError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        ApicError::MappingFailed(err)
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LOCAL_APIC: OnceCell<LocalApic> = OnceCell::uninit();
+static IO_APIC: Mutex<Option<IoApic>> = Mutex::new(None);
+
+pub struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    unsafe fn read(&self, register: usize) -> u32 {
+        read_volatile((self.base + register).as_ptr())
+    }
+
+    unsafe fn write(&self, register: usize, value: u32) {
+        write_volatile((self.base + register).as_mut_ptr(), value)
+    }
+
+    pub fn id(&self) -> u8 {
+        unsafe { (self.read(LAPIC_ID) >> 24) as u8 }
+    }
+
+    /// Software enable the APIC, and accept interrupts of all priorities
+    unsafe fn enable(&self) {
+        self.write(LAPIC_TASK_PRIORITY, 0);
+        self.write(LAPIC_SPURIOUS, 0x100 | SPURIOUS_VECTOR as u32);
+    }
+
+    pub fn end_of_interrupt(&self) {
+        unsafe { self.write(LAPIC_EOI, 0) };
+    }
+
+    /// Count how many timer ticks pass in 10ms, measured with the PIT
+    unsafe fn calibrate_timer(&self) -> u32 {
+        self.write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(LAPIC_LVT_TIMER, LVT_MASKED);
+        self.write(LAPIC_TIMER_INITIAL, u32::MAX);
+        pit::sleep_ms(10);
+        let remaining = self.read(LAPIC_TIMER_CURRENT);
+        self.write(LAPIC_TIMER_INITIAL, 0);
+        u32::MAX - remaining
+    }
+
+    /// Start the timer in periodic mode, firing `vector` at `frequency` Hz
+    unsafe fn start_timer(&self, vector: u8, frequency: u32) {
+        let ticks_per_10ms = self.calibrate_timer();
+        let initial_count = ticks_per_10ms / 10 * 1000 / frequency;
+        self.write(LAPIC_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        self.write(LAPIC_LVT_TIMER, LVT_TIMER_PERIODIC | vector as u32);
+        self.write(LAPIC_TIMER_INITIAL, initial_count.max(1));
+    }
+}
+
+pub struct IoApic {
+    base: VirtAddr,
+    gsi_base: u32,
+    overrides: Vec<InterruptOverride>,
+}
+
+impl IoApic {
+    unsafe fn read(&mut self, register: u32) -> u32 {
+        write_volatile(self.base.as_mut_ptr::<u32>(), register);
+        read_volatile((self.base + 0x10u64).as_ptr())
+    }
+
+    unsafe fn write(&mut self, register: u32, value: u32) {
+        write_volatile(self.base.as_mut_ptr::<u32>(), register);
+        write_volatile((self.base + 0x10u64).as_mut_ptr(), value);
+    }
+
+    /// Number of interrupt inputs handled by this IO APIC
+    pub fn redirection_entries(&mut self) -> u32 {
+        unsafe { ((self.read(IOAPIC_VERSION) >> 16) & 0xff) + 1 }
+    }
+
+    unsafe fn write_redirection(&mut self, gsi: u32, entry: u64) {
+        let register = IOAPIC_REDIRECTION_TABLE + (gsi - self.gsi_base) * 2;
+        self.write(register, entry as u32);
+        self.write(register + 1, (entry >> 32) as u32);
+    }
+
+    fn mask_all(&mut self) {
+        for i in 0..self.redirection_entries() {
+            unsafe { self.write_redirection(self.gsi_base + i, REDIRECTION_MASKED) };
+        }
+    }
+
+    /// Route ISA `irq` to `vector` on the local APIC with ID `destination`,
+    /// taking interrupt source overrides into account
+    pub fn route(&mut self, irq: u8, vector: u8, destination: u8) {
+        let mut entry = vector as u64 | (destination as u64) << 56;
+        let gsi = match self.overrides.iter().find(|o| o.irq == irq) {
+            Some(o) => {
+                if o.active_low() {
+                    entry |= REDIRECTION_ACTIVE_LOW;
+                }
+                if o.level_triggered() {
+                    entry |= REDIRECTION_LEVEL_TRIGGERED;
+                }
+                o.gsi
+            }
+            None => irq as u32,
+        };
+        unsafe { self.write_redirection(gsi, entry) };
+    }
+}
+
+/// Mask every line on both legacy PICs. They're still initialized first, so
+/// any interrupt that's already in flight arrives on a remapped vector
+fn disable_pics() {
+    unsafe {
+        Port::<u8>::new(0x21).write(0xff);
+        Port::<u8>::new(0xa1).write(0xff);
+    }
+}
+
+fn is_supported() -> bool {
+    let cpuid = unsafe { __cpuid(1) };
+    cpuid.edx & (1 << 9) != 0
+}
+
+/// Switch interrupt handling from the legacy PICs to the APIC.
+///
+/// Parses the MADT to locate the local APIC and the IO APIC, masks the PICs,
+/// starts the local APIC timer on the timer vector and routes every IRQ that
+/// has a handler registered through the IO APIC. If this fails, nothing has
+/// been changed and the PICs remain in use.
+pub fn init(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), ApicError> {
+    if !is_supported() {
+        return Err(ApicError::Unsupported);
+    }
+    let madt = acpi::parse_madt()?;
+    let has_legacy_pics = madt.has_legacy_pics;
+    let io_apic_entry = madt
+        .io_apics
+        .iter()
+        .find(|io| io.gsi_base == 0)
+        .ok_or(ApicError::NoIoApic)?;
+
+    let local_apic = LocalApic {
+        base: memory::map_mmio(madt.local_apic_address, 0x1000, mapper, frame_allocator)?,
+    };
+    let mut io_apic = IoApic {
+        base: memory::map_mmio(io_apic_entry.address, 0x20, mapper, frame_allocator)?,
+        gsi_base: io_apic_entry.gsi_base,
+        overrides: madt.overrides,
+    };
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        if has_legacy_pics {
+            disable_pics();
+        }
+        io_apic.mask_all();
+
+        let local_apic = LOCAL_APIC.get_or_init(|| local_apic);
+        unsafe {
+            local_apic.enable();
+            local_apic.start_timer(InterruptIndex::Timer.as_u8(), pit::TIMER_FREQUENCY);
+        }
+        let destination = local_apic.id();
+        for irq in interrupts::registered_irqs().filter(|irq| *irq != pit::TIMER_IRQ) {
+            io_apic.route(irq, interrupts::irq_vector(irq), destination);
+        }
+
+        *IO_APIC.lock() = Some(io_apic);
+        ENABLED.store(true, Ordering::SeqCst);
+    });
+    Ok(())
+}
+
+/// Whether interrupts are currently delivered through the APIC
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Index of the CPU this runs on into per-CPU state. Always 0 until the local
+/// APIC is set up
+pub fn cpu_index() -> usize {
+    LOCAL_APIC
+        .try_get()
+        .map_or(0, |local_apic| local_apic.id() as usize % MAX_CPUS)
+}
+
+/// Route `irq` through the IO APIC to its vector on this CPU
+pub fn enable_irq(irq: u8) {
+    // The PIT is left unrouted, as the local APIC timer delivers the timer
+    // vector in its place
+    if irq == pit::TIMER_IRQ {
+        return;
+    }
+    let destination = match LOCAL_APIC.try_get() {
+        Ok(local_apic) => local_apic.id(),
+        Err(_) => return,
+    };
+    if let Some(io_apic) = IO_APIC.lock().as_mut() {
+        io_apic.route(irq, interrupts::irq_vector(irq), destination);
+    }
+}
+
+/// Signal end of interrupt to the local APIC
+pub fn end_of_interrupt() {
+    if let Ok(local_apic) = LOCAL_APIC.try_get() {
+        local_apic.end_of_interrupt();
+    }
+}
@@ -0,0 +1,185 @@
+use conquer_once::spin::OnceCell;
+
+use crate::{
+    allocator::{self, AllocatorKind},
+    console::Backend,
+    logger::{Filter, FilterError},
+};
+
+/// Command line embedded at build time from the `BLIGHT_CMDLINE` environment
+/// variable, like `heap=1M log=warn,task=debug console=text`. The bootloader
+/// doesn't pass one, so this is the only source for now
+pub const BUILT_IN: &str = match option_env!("BLIGHT_CMDLINE") {
+    Some(cmdline) => cmdline,
+    None => "",
+};
+
+pub const MIN_HEAP_SIZE: usize = 16 * 1024;
+pub const MAX_HEAP_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamError {
+    /// Not a `key=value` pair
+    Malformed,
+    UnknownKey,
+    InvalidValue,
+    Filter(FilterError),
+}
+
+impl From<FilterError> for ParamError {
+    fn from(err: FilterError) -> Self {
+        ParamError::Filter(err)
+    }
+}
+
+/// Settings taken from the kernel command line. Options that aren't given
+/// keep the defaults from `new`
+#[derive(Debug, Clone, Copy)]
+pub struct BootParams {
+    /// `heap=<bytes>`, with an optional `K` or `M` suffix. Rounded up to whole
+    /// pages
+    pub heap_size: usize,
+    /// `allocator=bump|linked_list|sorted_linked_list`
+    pub allocator: AllocatorKind,
+    /// `log=<filter>`, in the format `logger::Filter::parse` takes
+    pub log_filter: Option<Filter>,
+    /// `console=text|framebuffer`
+    pub console: Backend,
+    /// `apic=on|off`. With it off, interrupts go through the legacy PICs
+    pub apic: bool,
+}
+
+impl BootParams {
+    pub const fn new() -> BootParams {
+        BootParams {
+            heap_size: allocator::DEFAULT_HEAP_SIZE,
+            allocator: AllocatorKind::SortedLinkedList,
+            log_filter: None,
+            console: Backend::Framebuffer,
+            apic: true,
+        }
+    }
+
+    /// Apply every whitespace separated option in `cmdline`, logging and
+    /// skipping the ones that can't be applied
+    pub fn parse(cmdline: &'static str) -> BootParams {
+        let mut params = BootParams::new();
+        for option in cmdline.split_whitespace() {
+            if let Err(err) = params.set(option) {
+                log::warn!("Ignoring boot option {:?}: {:?}", option, err);
+            }
+        }
+        params
+    }
+
+    /// Apply a single `key=value` option
+    pub fn set(&mut self, option: &'static str) -> Result<(), ParamError> {
+        let (key, value) = option.split_once('=').ok_or(ParamError::Malformed)?;
+        match key {
+            "heap" => self.heap_size = parse_heap_size(value)?,
+            "allocator" => {
+                self.allocator = match value {
+                    "bump" => AllocatorKind::Bump,
+                    "linked_list" => AllocatorKind::LinkedList,
+                    "sorted_linked_list" => AllocatorKind::SortedLinkedList,
+                    _ => return Err(ParamError::InvalidValue),
+                }
+            }
+            "log" => self.log_filter = Some(Filter::parse(value)?),
+            "console" => {
+                self.console = match value {
+                    "text" => Backend::Text,
+                    "framebuffer" => Backend::Framebuffer,
+                    _ => return Err(ParamError::InvalidValue),
+                }
+            }
+            "apic" => self.apic = parse_switch(value)?,
+            _ => return Err(ParamError::UnknownKey),
+        }
+        Ok(())
+    }
+}
+
+impl Default for BootParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_heap_size(value: &str) -> Result<usize, ParamError> {
+    let (digits, unit) = match value.as_bytes().last() {
+        Some(b'K') | Some(b'k') => (&value[..value.len() - 1], 1024),
+        Some(b'M') | Some(b'm') => (&value[..value.len() - 1], 1024 * 1024),
+        _ => (value, 1),
+    };
+    let size = digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|size| size.checked_mul(unit))
+        .filter(|size| (MIN_HEAP_SIZE..=MAX_HEAP_SIZE).contains(size))
+        .ok_or(ParamError::InvalidValue)?;
+    Ok(allocator::align_up(size, 4096))
+}
+
+fn parse_switch(value: &str) -> Result<bool, ParamError> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(ParamError::InvalidValue),
+    }
+}
+
+static PARAMS: OnceCell<BootParams> = OnceCell::uninit();
+static DEFAULTS: BootParams = BootParams::new();
+
+/// Parse `cmdline` into the parameters returned by `get`. Only the first call
+/// has any effect
+pub fn init(cmdline: &'static str) {
+    let _ = PARAMS.try_init_once(|| BootParams::parse(cmdline));
+}
+
+/// Parameters the kernel was booted with, or the defaults before `init`
+pub fn get() -> &'static BootParams {
+    PARAMS.try_get().unwrap_or(&DEFAULTS)
+}
+
+// Tests
+
+#[test_case]
+fn options_are_applied() {
+    let params = BootParams::parse("heap=1M  allocator=bump console=text apic=off log=debug");
+    assert_eq!(params.heap_size, 1024 * 1024);
+    assert_eq!(params.allocator, AllocatorKind::Bump);
+    assert_eq!(params.console, Backend::Text);
+    assert!(!params.apic);
+    let filter = params.log_filter.unwrap();
+    assert_eq!(filter.max_level(), log::LevelFilter::Debug);
+}
+
+#[test_case]
+fn heap_sizes_are_checked() {
+    let mut params = BootParams::new();
+    assert_eq!(params.set("heap=100000"), Ok(()));
+    assert_eq!(params.heap_size, 25 * 4096);
+    assert_eq!(params.set("heap=4K"), Err(ParamError::InvalidValue));
+    assert_eq!(params.set("heap=1G"), Err(ParamError::InvalidValue));
+    assert_eq!(params.set("heap=lots"), Err(ParamError::InvalidValue));
+    assert_eq!(params.heap_size, 25 * 4096);
+}
+
+#[test_case]
+fn bad_options_are_rejected() {
+    let mut params = BootParams::new();
+    assert_eq!(params.set("quiet"), Err(ParamError::Malformed));
+    assert_eq!(params.set("colour=on"), Err(ParamError::UnknownKey));
+    assert_eq!(params.set("apic=maybe"), Err(ParamError::InvalidValue));
+    assert_eq!(
+        params.set("log=task=loud"),
+        Err(ParamError::Filter(FilterError::UnknownLevel))
+    );
+    // Nothing was changed by the failed options
+    assert!(params.apic);
+    assert!(params.log_filter.is_none());
+}
+
+// end of tests
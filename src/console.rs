@@ -0,0 +1,456 @@
+use alloc::string::String;
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use self::ring::RingBuffer;
+use crate::{framebuffer, serial, vga_buffer};
+
+pub mod ring;
+
+/// Most sinks that can be registered at once
+pub const MAX_SINKS: usize = 8;
+/// Bytes of output kept by `RING`
+pub const RING_SIZE: usize = 16 * 1024;
+
+/// Somewhere `print!` output goes. Sinks that are a screen of character
+/// cells also report their size and cursor, and can be cleared
+pub trait ConsoleSink: Sync {
+    /// Unique name the sink is enabled and disabled by
+    fn name(&self) -> &'static str;
+
+    /// Write some output. Called with interrupts disabled
+    fn write_str(&self, s: &str);
+
+    // Unlike `write_str`, the screen methods can be called with interrupts
+    // enabled, and disable them where they need to
+
+    /// Size of the screen as `(rows, columns)`, or `None` if the sink isn't a
+    /// screen
+    fn size(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Cursor position as `(row, column)`, or `None` if the sink isn't a
+    /// screen
+    fn position(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Move the cursor, clamped to the screen
+    fn set_position(&self, _row: usize, _column: usize) {}
+
+    /// Blank the screen and move the cursor to the top left corner
+    fn clear(&self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The 80x25 VGA text buffer, with virtual terminals
+    Text,
+    /// Text drawn on the framebuffer set up by `framebuffer::init`
+    Framebuffer,
+}
+
+impl Backend {
+    /// The sink that writes to this backend
+    pub fn sink(self) -> &'static dyn ConsoleSink {
+        match self {
+            Backend::Text => &vga_buffer::TEXT_SINK,
+            Backend::Framebuffer => &framebuffer::console::FRAMEBUFFER_SINK,
+        }
+    }
+}
+
+/// Backend screen output currently goes to
+pub fn backend() -> Backend {
+    match is_enabled(Backend::Framebuffer.sink().name()) {
+        Some(true) => Backend::Framebuffer,
+        _ => Backend::Text,
+    }
+}
+
+/// Send all further screen output to `backend`, by enabling its sink and
+/// disabling the other one. Returns false, leaving the console as it is, if
+/// the backend hasn't been set up
+pub fn select(backend: Backend) -> bool {
+    if backend == Backend::Framebuffer && framebuffer::console::CONSOLE.get().is_none() {
+        return false;
+    }
+    without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        for entry in sinks.iter_mut().flatten() {
+            for &screen in &[Backend::Text, Backend::Framebuffer] {
+                if entry.sink.name() == screen.sink().name() {
+                    entry.enabled = screen == backend;
+                }
+            }
+        }
+    });
+    true
+}
+
+/// Sink of the selected screen backend, for output that only belongs on the
+/// screen
+pub fn screen() -> &'static dyn ConsoleSink {
+    backend().sink()
+}
+
+/// Clear every enabled sink that can be cleared
+pub fn clear() {
+    without_interrupts(|| {
+        let sinks = *SINKS.lock();
+        for entry in sinks.iter().flatten().filter(|entry| entry.enabled) {
+            entry.sink.clear();
+        }
+    });
+}
+
+/// Writes to the first serial port
+pub struct SerialSink;
+
+impl ConsoleSink for SerialSink {
+    fn name(&self) -> &'static str {
+        "serial"
+    }
+
+    fn write_str(&self, s: &str) {
+        serial::SERIAL1
+            .lock()
+            .write_str(s)
+            .expect("Failed writing to SERIAL1");
+    }
+
+    fn clear(&self) {
+        // Understood by any terminal emulator on the other end
+        self.write_str("\x1b[2J\x1b[H");
+    }
+}
+
+/// Keeps the most recent output in memory, so it can be looked at later
+pub struct RingSink {
+    buffer: Mutex<RingBuffer<RING_SIZE>>,
+}
+
+impl RingSink {
+    pub const fn new() -> Self {
+        RingSink {
+            buffer: Mutex::new(RingBuffer::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        without_interrupts(|| self.buffer.lock().len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        without_interrupts(|| self.buffer.lock().clear());
+    }
+
+    /// Copy the newest output into `out`, returning how many bytes were
+    /// copied
+    pub fn read_newest(&self, out: &mut [u8]) -> usize {
+        without_interrupts(|| self.buffer.lock().read_newest(out))
+    }
+
+    /// Everything kept, oldest first. Characters cut in half by the start of
+    /// the buffer are replaced
+    pub fn contents(&self) -> String {
+        let mut bytes = alloc::vec![0; RING_SIZE];
+        let len = without_interrupts(|| self.buffer.lock().read(&mut bytes));
+        String::from_utf8_lossy(&bytes[..len]).into_owned()
+    }
+}
+
+impl Default for RingSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsoleSink for RingSink {
+    fn name(&self) -> &'static str {
+        "ring"
+    }
+
+    fn write_str(&self, s: &str) {
+        self.buffer.lock().write(s.as_bytes());
+    }
+}
+
+pub static SERIAL: SerialSink = SerialSink;
+pub static RING: RingSink = RingSink::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkError {
+    Full,
+    AlreadyRegistered,
+    NotFound,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    sink: &'static dyn ConsoleSink,
+    enabled: bool,
+}
+
+impl Entry {
+    const fn enabled(sink: &'static dyn ConsoleSink) -> Option<Entry> {
+        Some(Entry {
+            sink,
+            enabled: true,
+        })
+    }
+
+    const fn disabled(sink: &'static dyn ConsoleSink) -> Option<Entry> {
+        Some(Entry {
+            sink,
+            enabled: false,
+        })
+    }
+}
+
+// Locked by `print!` from interrupt handlers, so it must only be locked with
+// interrupts disabled. The serial sink is left for the kernel to enable, so
+// nothing gets mixed into test results on the serial port
+static SINKS: Mutex<[Option<Entry>; MAX_SINKS]> = Mutex::new([
+    Entry::enabled(&vga_buffer::TEXT_SINK),
+    Entry::disabled(&framebuffer::console::FRAMEBUFFER_SINK),
+    Entry::disabled(&SERIAL),
+    Entry::enabled(&RING),
+    None,
+    None,
+    None,
+    None,
+]);
+
+/// Start sending output to `sink` as well
+pub fn register(sink: &'static dyn ConsoleSink) -> Result<(), SinkError> {
+    without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        if sinks
+            .iter()
+            .flatten()
+            .any(|entry| entry.sink.name() == sink.name())
+        {
+            return Err(SinkError::AlreadyRegistered);
+        }
+        let slot = sinks.iter_mut().find(|slot| slot.is_none());
+        *slot.ok_or(SinkError::Full)? = Entry::enabled(sink);
+        Ok(())
+    })
+}
+
+pub fn unregister(name: &str) -> Result<(), SinkError> {
+    without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        let slot = sinks
+            .iter_mut()
+            .find(|slot| matches!(slot, Some(entry) if entry.sink.name() == name));
+        *slot.ok_or(SinkError::NotFound)? = None;
+        Ok(())
+    })
+}
+
+/// Turn output to the sink called `name` on or off, keeping it registered
+pub fn set_enabled(name: &str, enabled: bool) -> Result<(), SinkError> {
+    without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        let entry = sinks
+            .iter_mut()
+            .flatten()
+            .find(|entry| entry.sink.name() == name)
+            .ok_or(SinkError::NotFound)?;
+        entry.enabled = enabled;
+        Ok(())
+    })
+}
+
+/// Whether the sink called `name` is enabled, or `None` if there's no such
+/// sink
+pub fn is_enabled(name: &str) -> Option<bool> {
+    without_interrupts(|| {
+        SINKS
+            .lock()
+            .iter()
+            .flatten()
+            .find(|entry| entry.sink.name() == name)
+            .map(|entry| entry.enabled)
+    })
+}
+
+/// Call `f` with the name of every registered sink and whether it's enabled
+pub fn for_each_sink(mut f: impl FnMut(&'static str, bool)) {
+    let sinks = without_interrupts(|| *SINKS.lock());
+    for entry in sinks.iter().flatten() {
+        f(entry.sink.name(), entry.enabled);
+    }
+}
+
+/// Adapts a sink to `fmt::Write`
+struct SinkWriter(&'static dyn ConsoleSink);
+
+impl fmt::Write for SinkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s);
+        Ok(())
+    }
+}
+
+/// Print to every enabled sink
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::console::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    without_interrupts(|| {
+        // Copied so a sink printing doesn't deadlock on the registry
+        let sinks = *SINKS.lock();
+        for entry in sinks.iter().flatten().filter(|entry| entry.enabled) {
+            SinkWriter(entry.sink).write_fmt(args).unwrap();
+        }
+    });
+}
+
+/// Release every lock console output goes through, whoever holds them.
+///
+/// # Safety
+///
+/// Whatever held the locks must never run again, or it would be writing
+/// alongside the next holder. Only for when the machine is going down
+pub unsafe fn force_unlock() {
+    SINKS.force_unlock();
+    RING.buffer.force_unlock();
+    serial::SERIAL1.force_unlock();
+    for terminal in vga_buffer::TERMINALS.iter() {
+        terminal.force_unlock();
+    }
+    if let Ok(console) = framebuffer::console::CONSOLE.try_get() {
+        console.force_unlock();
+    }
+    if let Ok(framebuffer) = framebuffer::FRAMEBUFFER.try_get() {
+        framebuffer.force_unlock();
+    }
+}
+
+/// Print from a panic or a fatal exception, where the code that was running
+/// may hold the console locks and never let go of them
+#[macro_export]
+macro_rules! emergency_println {
+    () => ($crate::console::_emergency_print(format_args!("\n")));
+    ($($arg:tt)*) => ($crate::console::_emergency_print(
+        format_args!("{}\n", format_args!($($arg)*))));
+}
+
+/// Set while an emergency print is going through the sinks
+static IN_EMERGENCY: AtomicBool = AtomicBool::new(false);
+
+/// Interrupts are left disabled, as the machine isn't expected to go on
+#[doc(hidden)]
+pub fn _emergency_print(args: fmt::Arguments) {
+    x86_64::instructions::interrupts::disable();
+    if IN_EMERGENCY.swap(true, Ordering::SeqCst) {
+        // A sink panicked while printing the last one, so only the serial
+        // port can be relied on
+        let _ = serial::EmergencyWriter.write_fmt(args);
+        return;
+    }
+    unsafe { force_unlock() };
+    _print(args);
+    IN_EMERGENCY.store(false, Ordering::SeqCst);
+}
+
+// Tests
+
+#[test_case]
+fn text_console_is_the_default() {
+    assert_eq!(backend(), Backend::Text);
+    // Not set up in the unit tests
+    assert!(!select(Backend::Framebuffer));
+    assert_eq!(backend(), Backend::Text);
+}
+
+#[test_case]
+fn console_reports_text_mode_size() {
+    let console = screen();
+    assert_eq!(console.name(), "text");
+    assert_eq!(console.size(), Some((25, 80)));
+    let (row, _) = console.position().unwrap();
+    console.set_position(row, 3);
+    assert_eq!(console.position(), Some((row, 3)));
+    console.set_position(row, 0);
+    assert_eq!(SERIAL.size(), None);
+}
+
+#[cfg(test)]
+struct CountingSink(core::sync::atomic::AtomicUsize);
+
+#[cfg(test)]
+impl ConsoleSink for CountingSink {
+    fn name(&self) -> &'static str {
+        "counting"
+    }
+
+    fn write_str(&self, s: &str) {
+        self.0.fetch_add(s.len(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+static COUNTING: CountingSink = CountingSink(core::sync::atomic::AtomicUsize::new(0));
+
+#[test_case]
+fn output_reaches_every_enabled_sink() {
+    let count = || COUNTING.0.load(Ordering::Relaxed);
+    register(&COUNTING).unwrap();
+    assert_eq!(register(&COUNTING), Err(SinkError::AlreadyRegistered));
+    assert_eq!(is_enabled("counting"), Some(true));
+    print!("abc");
+    assert_eq!(count(), 3);
+
+    set_enabled("counting", false).unwrap();
+    println!("Not counted");
+    assert_eq!(count(), 3);
+
+    unregister("counting").unwrap();
+    assert_eq!(is_enabled("counting"), None);
+    assert_eq!(set_enabled("counting", true), Err(SinkError::NotFound));
+}
+
+#[test_case]
+fn ring_keeps_recent_output() {
+    println!("Kept in the ring");
+    let mut tail = [0; 17];
+    assert_eq!(RING.read_newest(&mut tail), tail.len());
+    assert_eq!(&tail, b"Kept in the ring\n");
+}
+
+#[test_case]
+fn builtin_sinks_are_registered() {
+    let mut names = [""; MAX_SINKS];
+    let mut count = 0;
+    for_each_sink(|name, _| {
+        names[count] = name;
+        count += 1;
+    });
+    assert_eq!(names[..4], ["text", "framebuffer", "serial", "ring"]);
+    // Only enabled by the kernel itself
+    assert_eq!(is_enabled("serial"), Some(false));
+}
+
+// end of tests
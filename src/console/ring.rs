@@ -0,0 +1,139 @@
+use core::fmt;
+
+/// Fixed size byte buffer keeping the most recent `N` bytes written to it
+pub struct RingBuffer<const N: usize> {
+    data: [u8; N],
+    /// Index of the oldest byte
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        RingBuffer {
+            data: [0; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+
+    /// Append `bytes`, overwriting the oldest ones once full
+    pub fn write(&mut self, bytes: &[u8]) {
+        // Only the last N bytes can survive anyway
+        let bytes = &bytes[bytes.len().saturating_sub(N)..];
+        for &byte in bytes {
+            let end = (self.start + self.len) % N;
+            self.data[end] = byte;
+            if self.len < N {
+                self.len += 1;
+            } else {
+                self.start = (self.start + 1) % N;
+            }
+        }
+    }
+
+    /// Copy the oldest bytes into `out`, returning how many were copied
+    pub fn read(&self, out: &mut [u8]) -> usize {
+        let count = self.len.min(out.len());
+        let (first, second) = self.as_slices();
+        let from_first = count.min(first.len());
+        out[..from_first].copy_from_slice(&first[..from_first]);
+        out[from_first..count].copy_from_slice(&second[..count - from_first]);
+        count
+    }
+
+    /// Copy the newest bytes into `out`, returning how many were copied
+    pub fn read_newest(&self, out: &mut [u8]) -> usize {
+        let count = self.len.min(out.len());
+        let (first, second) = self.as_slices();
+        let newest = first.iter().chain(second).skip(self.len - count);
+        for (target, byte) in out.iter_mut().zip(newest) {
+            *target = *byte;
+        }
+        count
+    }
+
+    /// Contents, oldest first, in the two pieces they're stored as
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let end = self.start + self.len;
+        if end <= N {
+            (&self.data[self.start..end], &[])
+        } else {
+            (&self.data[self.start..], &self.data[..end - N])
+        }
+    }
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for RingBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+// Tests
+
+#[test_case]
+fn bytes_are_kept_in_order() {
+    let mut ring = RingBuffer::<8>::new();
+    assert!(ring.is_empty());
+    ring.write(b"abc");
+    ring.write(b"de");
+    let mut out = [0; 8];
+    assert_eq!(ring.read(&mut out), 5);
+    assert_eq!(&out[..5], b"abcde");
+}
+
+#[test_case]
+fn oldest_bytes_are_overwritten() {
+    let mut ring = RingBuffer::<8>::new();
+    ring.write(b"abcdef");
+    ring.write(b"ghij");
+    assert_eq!(ring.len(), 8);
+    assert_eq!(ring.as_slices(), (&b"cdefgh"[..], &b"ij"[..]));
+    let mut out = [0; 8];
+    assert_eq!(ring.read(&mut out), 8);
+    assert_eq!(&out, b"cdefghij");
+
+    ring.write(b"0123456789");
+    assert_eq!(ring.read(&mut out), 8);
+    assert_eq!(&out, b"23456789");
+}
+
+#[test_case]
+fn short_reads_take_either_end() {
+    let mut ring = RingBuffer::<4>::new();
+    ring.write(b"abcdef");
+    let mut out = [0; 3];
+    assert_eq!(ring.read(&mut out), 3);
+    assert_eq!(&out, b"cde");
+    assert_eq!(ring.read_newest(&mut out), 3);
+    assert_eq!(&out, b"def");
+    ring.clear();
+    assert_eq!(ring.read(&mut out), 0);
+}
+
+// end of tests
@@ -0,0 +1,200 @@
+use conquer_once::spin::OnceCell;
+use spin::Mutex;
+use x86_64::{
+    instructions::port::Port,
+    structures::paging::{mapper::MapToError, FrameAllocator, Mapper, Size4KiB},
+    PhysAddr,
+};
+
+use crate::{
+    graphics::{Canvas, Rect},
+    memory, pci,
+};
+
+pub mod console;
+pub mod font;
+
+// Bochs VBE ("DISPI") interface, provided by QEMU's standard VGA and by Bochs
+const DISPI_INDEX: u16 = 0x01ce;
+const DISPI_DATA: u16 = 0x01cf;
+
+const DISPI_ID: u16 = 0x0;
+const DISPI_XRES: u16 = 0x1;
+const DISPI_YRES: u16 = 0x2;
+const DISPI_BPP: u16 = 0x3;
+const DISPI_ENABLE: u16 = 0x4;
+const DISPI_VIRT_WIDTH: u16 = 0x6;
+const DISPI_X_OFFSET: u16 = 0x8;
+const DISPI_Y_OFFSET: u16 = 0x9;
+
+/// Oldest interface version with 32 bit colour
+const DISPI_ID_32BPP: u16 = 0xb0c4;
+const DISPI_ID_LATEST: u16 = 0xb0c5;
+const DISPI_ENABLED: u16 = 0x01;
+const DISPI_LFB_ENABLED: u16 = 0x40;
+
+const BITS_PER_PIXEL: u16 = 32;
+
+/// PCI IDs of the Bochs/QEMU display adapter, whose first BAR is the linear
+/// framebuffer
+const VGA_VENDOR_ID: u16 = 0x1234;
+const VGA_DEVICE_ID: u16 = 0x1111;
+
+/// The framebuffer, once a graphics mode has been set
+pub static FRAMEBUFFER: OnceCell<Mutex<Framebuffer>> = OnceCell::uninit();
+
+#[derive(Debug)]
+pub enum FramebufferError {
+    /// The display adapter doesn't implement the Bochs VBE interface
+    Unsupported(u16),
+    NoDevice,
+    AlreadyInitialised,
+    MappingFailed(MapToError<Size4KiB>),
+}
+
+impl From<MapToError<Size4KiB>> for FramebufferError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        FramebufferError::MappingFailed(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb {
+        Rgb { r, g, b }
+    }
+
+    /// Pixel value in the framebuffer's `0x00RRGGBB` format
+    pub const fn to_pixel(self) -> u32 {
+        (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
+    }
+
+    pub const fn from_pixel(pixel: u32) -> Rgb {
+        Rgb::new((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+    }
+
+    /// `other` drawn over this colour with opacity `alpha`
+    pub fn blend(self, other: Rgb, alpha: u8) -> Rgb {
+        let mix = |below: u8, above: u8| {
+            ((above as u32 * alpha as u32 + below as u32 * (255 - alpha as u32)) / 255) as u8
+        };
+        Rgb::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+}
+
+/// A linear 32 bits per pixel framebuffer
+pub struct Framebuffer {
+    pixels: &'static mut [u32],
+    width: usize,
+    height: usize,
+    /// Pixels from the start of one line to the start of the next
+    stride: usize,
+}
+
+impl Framebuffer {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Move everything up by `lines` lines of pixels, filling the space left
+    /// at the bottom with `fill`
+    pub fn scroll_up(&mut self, lines: usize, fill: Rgb) {
+        let lines = lines.min(self.height);
+        let kept = (self.height - lines) * self.stride;
+        self.pixels
+            .copy_within(lines * self.stride..lines * self.stride + kept, 0);
+        let top = (self.height - lines) as i32;
+        self.fill_rect(Rect::new(0, top, self.width as u32, lines as u32), fill);
+    }
+}
+
+impl Canvas for Framebuffer {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn row(&self, y: usize) -> &[u32] {
+        &self.pixels[y * self.stride..y * self.stride + self.width]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u32] {
+        &mut self.pixels[y * self.stride..y * self.stride + self.width]
+    }
+}
+
+fn write_dispi(register: u16, value: u16) {
+    unsafe {
+        Port::<u16>::new(DISPI_INDEX).write(register);
+        Port::<u16>::new(DISPI_DATA).write(value);
+    }
+}
+
+fn read_dispi(register: u16) -> u16 {
+    unsafe {
+        Port::<u16>::new(DISPI_INDEX).write(register);
+        Port::<u16>::new(DISPI_DATA).read()
+    }
+}
+
+/// Switch to a `width` by `height` graphics mode and set up the framebuffer
+/// console on it
+pub fn init(
+    width: u16,
+    height: u16,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), FramebufferError> {
+    if FRAMEBUFFER.is_initialized() {
+        return Err(FramebufferError::AlreadyInitialised);
+    }
+    let id = read_dispi(DISPI_ID);
+    if !(DISPI_ID_32BPP..=DISPI_ID_LATEST).contains(&id) {
+        return Err(FramebufferError::Unsupported(id));
+    }
+    let device = pci::find(VGA_VENDOR_ID, VGA_DEVICE_ID).ok_or(FramebufferError::NoDevice)?;
+
+    let (width, height) = (width as usize, height as usize);
+    let size = (width * height * 4) as u64;
+    let base = memory::map_mmio(
+        PhysAddr::new(device.memory_bar(0) as u64),
+        size,
+        mapper,
+        frame_allocator,
+    )?;
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        write_dispi(DISPI_ENABLE, 0);
+        write_dispi(DISPI_XRES, width as u16);
+        write_dispi(DISPI_YRES, height as u16);
+        write_dispi(DISPI_BPP, BITS_PER_PIXEL);
+        write_dispi(DISPI_VIRT_WIDTH, width as u16);
+        write_dispi(DISPI_X_OFFSET, 0);
+        write_dispi(DISPI_Y_OFFSET, 0);
+        write_dispi(DISPI_ENABLE, DISPI_ENABLED | DISPI_LFB_ENABLED);
+    });
+
+    let pixels = unsafe { core::slice::from_raw_parts_mut(base.as_mut_ptr(), width * height) };
+    let framebuffer = Framebuffer {
+        pixels,
+        width,
+        height,
+        stride: width,
+    };
+    FRAMEBUFFER.init_once(|| Mutex::new(framebuffer));
+    console::init();
+    Ok(())
+}
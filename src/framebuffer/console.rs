@@ -0,0 +1,321 @@
+use core::fmt;
+
+use conquer_once::spin::OnceCell;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::{font::Font, Framebuffer, Rgb, FRAMEBUFFER};
+use crate::{
+    console::ConsoleSink,
+    graphics::{Canvas, Rect, TextStyle},
+    vga_buffer::ansi::{Action, Csi, Parser},
+};
+
+/// The colours of VGA text mode, in ANSI order with the bright ones after
+#[rustfmt::skip]
+const PALETTE: [Rgb; 16] = [
+    Rgb::new(0x00, 0x00, 0x00), Rgb::new(0xaa, 0x00, 0x00),
+    Rgb::new(0x00, 0xaa, 0x00), Rgb::new(0xaa, 0x55, 0x00),
+    Rgb::new(0x00, 0x00, 0xaa), Rgb::new(0xaa, 0x00, 0xaa),
+    Rgb::new(0x00, 0xaa, 0xaa), Rgb::new(0xaa, 0xaa, 0xaa),
+    Rgb::new(0x55, 0x55, 0x55), Rgb::new(0xff, 0x55, 0x55),
+    Rgb::new(0x55, 0xff, 0x55), Rgb::new(0xff, 0xff, 0x55),
+    Rgb::new(0x55, 0x55, 0xff), Rgb::new(0xff, 0x55, 0xff),
+    Rgb::new(0x55, 0xff, 0xff), Rgb::new(0xff, 0xff, 0xff),
+];
+/// Added to a palette index to get the bright version of the colour
+const BRIGHT: usize = 8;
+// Same as the text mode console
+const DEFAULT_FOREGROUND: usize = 2;
+const DEFAULT_BACKGROUND: usize = 0;
+
+/// Pixel lines at the bottom of a cell taken up by the cursor
+const CURSOR_HEIGHT: usize = 2;
+/// Shown for characters that can't be printed
+const REPLACEMENT: char = '■';
+
+/// Text console on the framebuffer, set up by `framebuffer::init`
+pub static CONSOLE: OnceCell<Mutex<FramebufferConsole>> = OnceCell::uninit();
+
+pub(super) fn init() {
+    let font = Font::default_font();
+    let mut framebuffer = FRAMEBUFFER
+        .get()
+        .expect("Framebuffer not initialised")
+        .lock();
+    let rows = framebuffer.height() / font.height();
+    let columns = framebuffer.width() / font.width();
+    let mut console = FramebufferConsole::new(font, rows, columns);
+    console.clear_on(&mut framebuffer);
+    CONSOLE.init_once(|| Mutex::new(console));
+}
+
+/// Draws text with a bitmap font, understanding the same escape sequences as
+/// the VGA text console
+pub struct FramebufferConsole {
+    font: Font,
+    rows: usize,
+    columns: usize,
+    row: usize,
+    column: usize,
+    /// Palette indices of the current colours
+    foreground: usize,
+    background: usize,
+    /// Set by SGR 1, makes the foreground colour bright
+    bold: bool,
+    parser: Parser,
+    /// What the cursor was drawn with, so it can be removed again after the
+    /// colours have changed. 0 while it isn't drawn
+    cursor_mask: u32,
+}
+
+impl fmt::Write for FramebufferConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
+
+/// Console sink for `CONSOLE`, dropping output until it's set up
+pub struct FramebufferSink;
+
+pub static FRAMEBUFFER_SINK: FramebufferSink = FramebufferSink;
+
+impl FramebufferSink {
+    fn with_console<R>(&self, f: impl FnOnce(&mut FramebufferConsole) -> R) -> Option<R> {
+        let console = CONSOLE.get()?;
+        Some(without_interrupts(|| f(&mut console.lock())))
+    }
+}
+
+impl ConsoleSink for FramebufferSink {
+    fn name(&self) -> &'static str {
+        "framebuffer"
+    }
+
+    fn write_str(&self, s: &str) {
+        self.with_console(|console| console.write_string(s));
+    }
+
+    fn size(&self) -> Option<(usize, usize)> {
+        self.with_console(|console| (console.rows, console.columns))
+    }
+
+    fn position(&self) -> Option<(usize, usize)> {
+        self.with_console(|console| (console.row, console.column))
+    }
+
+    fn set_position(&self, row: usize, column: usize) {
+        self.with_console(|console| console.set_position(row, column));
+    }
+
+    fn clear(&self) {
+        self.with_console(|console| console.clear());
+    }
+}
+
+impl FramebufferConsole {
+    fn new(font: Font, rows: usize, columns: usize) -> FramebufferConsole {
+        FramebufferConsole {
+            font,
+            rows,
+            columns,
+            row: 0,
+            column: 0,
+            foreground: DEFAULT_FOREGROUND,
+            background: DEFAULT_BACKGROUND,
+            bold: false,
+            parser: Parser::new(),
+            cursor_mask: 0,
+        }
+    }
+
+    /// Run `f` on the framebuffer, with the cursor hidden
+    fn with_framebuffer<R>(&mut self, f: impl FnOnce(&mut Self, &mut Framebuffer) -> R) -> R {
+        let mut framebuffer = FRAMEBUFFER
+            .get()
+            .expect("Framebuffer not initialised")
+            .lock();
+        self.toggle_cursor(&mut framebuffer, self.cursor_mask);
+        let result = f(self, &mut framebuffer);
+        self.cursor_mask =
+            PALETTE[self.foreground].to_pixel() ^ PALETTE[self.background].to_pixel();
+        self.toggle_cursor(&mut framebuffer, self.cursor_mask);
+        result
+    }
+
+    /// Move the cursor, clamped to the screen
+    pub fn set_position(&mut self, row: usize, column: usize) {
+        // The cursor has to be redrawn at the new position
+        self.with_framebuffer(|console, _| {
+            console.row = row.min(console.rows - 1);
+            console.column = column.min(console.columns - 1);
+        });
+    }
+
+    /// Blank the screen and move the cursor to the top left corner
+    pub fn clear(&mut self) {
+        self.with_framebuffer(|console, framebuffer| console.clear_on(framebuffer));
+    }
+
+    /// Write a string, interpreting ANSI escape sequences
+    pub fn write_string(&mut self, string: &str) {
+        self.with_framebuffer(|console, framebuffer| {
+            for c in string.chars() {
+                if !c.is_ascii() {
+                    console.put_char(framebuffer, c);
+                    continue;
+                }
+                match console.parser.advance(c as u8) {
+                    Action::Print(b'\n') => console.new_line(framebuffer),
+                    // Backspace only moves the cursor, the character is left
+                    // in place
+                    Action::Print(0x08) => console.column = console.column.saturating_sub(1),
+                    Action::Print(byte @ 0x20..=0x7e) => {
+                        console.put_char(framebuffer, byte as char)
+                    }
+                    Action::Print(_) => console.put_char(framebuffer, REPLACEMENT),
+                    Action::Csi(csi) => console.execute_csi(framebuffer, &csi),
+                    Action::None => {}
+                }
+            }
+        });
+    }
+
+    fn put_char(&mut self, framebuffer: &mut Framebuffer, c: char) {
+        if self.column >= self.columns {
+            self.new_line(framebuffer);
+        }
+        let (x, y) = self.cell_origin(self.row, self.column);
+        let style = TextStyle::new(self.font, PALETTE[self.foreground])
+            .with_background(PALETTE[self.background]);
+        framebuffer.draw_char(x as i32, y as i32, c, &style);
+        self.column += 1;
+    }
+
+    fn new_line(&mut self, framebuffer: &mut Framebuffer) {
+        self.column = 0;
+        if self.row < self.rows - 1 {
+            self.row += 1;
+            return;
+        }
+        framebuffer.scroll_up(self.font.height(), PALETTE[self.background]);
+        // Whatever was below the last row of text scrolled into it
+        self.clear_cells(framebuffer, self.rows - 1, 0..self.columns);
+    }
+
+    /// Top left pixel of the cell at `row`, `column`
+    fn cell_origin(&self, row: usize, column: usize) -> (usize, usize) {
+        (column * self.font.width(), row * self.font.height())
+    }
+
+    fn clear_cells(
+        &self,
+        framebuffer: &mut Framebuffer,
+        row: usize,
+        columns: core::ops::Range<usize>,
+    ) {
+        let (x, y) = self.cell_origin(row, columns.start);
+        let width = columns.len() * self.font.width();
+        let area = Rect::new(x as i32, y as i32, width as u32, self.font.height() as u32);
+        framebuffer.fill_rect(area, PALETTE[self.background]);
+    }
+
+    fn clear_on(&mut self, framebuffer: &mut Framebuffer) {
+        framebuffer.fill_rect(framebuffer.bounds(), PALETTE[self.background]);
+        self.row = 0;
+        self.column = 0;
+    }
+
+    /// Flip the `mask` bits at the bottom of the cell under the cursor.
+    /// Doing it twice leaves the cell as it was
+    fn toggle_cursor(&self, framebuffer: &mut Framebuffer, mask: u32) {
+        let (x, y) = self.cell_origin(self.row, self.column.min(self.columns - 1));
+        let bottom = y + self.font.height();
+        for line in bottom - CURSOR_HEIGHT..bottom {
+            let start = line * framebuffer.stride + x;
+            for pixel in &mut framebuffer.pixels[start..start + self.font.width()] {
+                *pixel ^= mask;
+            }
+        }
+    }
+
+    fn execute_csi(&mut self, framebuffer: &mut Framebuffer, csi: &Csi) {
+        let (row, column) = (self.row, self.column.min(self.columns - 1));
+        let count = csi.param(0, 1) as usize;
+        match csi.command {
+            b'A' => self.row = row.saturating_sub(count),
+            b'B' => self.row = (row + count).min(self.rows - 1),
+            b'C' => self.column = (column + count).min(self.columns - 1),
+            b'D' => self.column = column.saturating_sub(count),
+            b'G' => self.column = (count - 1).min(self.columns - 1),
+            b'H' | b'f' => {
+                self.row = (count - 1).min(self.rows - 1);
+                self.column = (csi.param(1, 1) as usize - 1).min(self.columns - 1);
+            }
+            b'J' => self.erase_screen(framebuffer, csi.param(0, 0)),
+            b'K' => self.erase_line(framebuffer, csi.param(0, 0)),
+            b'm' => self.select_graphic_rendition(csi.params()),
+            _ => {}
+        }
+    }
+
+    /// Erase from the cursor to the end of the screen (0), from the start of
+    /// the screen to the cursor (1) or the whole screen (2)
+    fn erase_screen(&mut self, framebuffer: &mut Framebuffer, mode: u16) {
+        let rows = match mode {
+            0 => self.row + 1..self.rows,
+            1 => 0..self.row,
+            2 => 0..self.rows,
+            _ => return,
+        };
+        for row in rows {
+            self.clear_cells(framebuffer, row, 0..self.columns);
+        }
+        if mode != 2 {
+            self.erase_line(framebuffer, mode);
+        }
+    }
+
+    /// Erase from the cursor to the end of the line (0), from the start of the
+    /// line to the cursor (1) or the whole line (2)
+    fn erase_line(&mut self, framebuffer: &mut Framebuffer, mode: u16) {
+        let column = self.column.min(self.columns - 1);
+        let columns = match mode {
+            0 => column..self.columns,
+            1 => 0..column + 1,
+            2 => 0..self.columns,
+            _ => return,
+        };
+        self.clear_cells(framebuffer, self.row, columns);
+    }
+
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        let bright = |bold: bool| if bold { BRIGHT } else { 0 };
+        for &param in params {
+            let param = param as usize;
+            match param {
+                0 => {
+                    self.foreground = DEFAULT_FOREGROUND;
+                    self.background = DEFAULT_BACKGROUND;
+                    self.bold = false;
+                }
+                1 => {
+                    self.bold = true;
+                    self.foreground |= BRIGHT;
+                }
+                22 => {
+                    self.bold = false;
+                    self.foreground &= !BRIGHT;
+                }
+                30..=37 => self.foreground = param - 30 + bright(self.bold),
+                39 => self.foreground = DEFAULT_FOREGROUND + bright(self.bold),
+                40..=47 => self.background = param - 40,
+                49 => self.background = DEFAULT_BACKGROUND,
+                90..=97 => self.foreground = param - 90 + BRIGHT,
+                100..=107 => self.background = param - 100 + BRIGHT,
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+/// 8x16 font with code page 437 glyphs, made from the public domain
+/// misc-fixed 8x13 font padded to 16 rows
+const DEFAULT_FONT: &[u8] = include_bytes!("font.psf");
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_SIZE: usize = 4;
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TABLE: u8 = 0x02;
+/// Ends the list of code points of a glyph in the unicode table
+const PSF1_SEPARATOR: u16 = 0xffff;
+
+/// Glyphs in PSF1 fonts are always one byte wide
+pub const GLYPH_WIDTH: usize = 8;
+
+/// Shown for characters the font has no glyph for
+const REPLACEMENT: char = '■';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    BadMagic,
+    Truncated,
+}
+
+/// A PC Screen Font (version 1). Every glyph is 8 pixels wide, one byte per
+/// row with the leftmost pixel in the top bit
+#[derive(Debug, Clone, Copy)]
+pub struct Font {
+    height: usize,
+    glyph_count: usize,
+    glyphs: &'static [u8],
+    /// Code points of each glyph, as 16 bit little endian values
+    unicode: Option<&'static [u8]>,
+}
+
+impl Font {
+    pub fn parse(data: &'static [u8]) -> Result<Font, FontError> {
+        if data.len() < PSF1_HEADER_SIZE {
+            return Err(FontError::Truncated);
+        }
+        if data[..2] != PSF1_MAGIC {
+            return Err(FontError::BadMagic);
+        }
+        let mode = data[2];
+        let height = data[3] as usize;
+        let glyph_count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let glyphs_end = PSF1_HEADER_SIZE + glyph_count * height;
+        if data.len() < glyphs_end {
+            return Err(FontError::Truncated);
+        }
+        let unicode = if mode & PSF1_MODE_HAS_TABLE != 0 {
+            Some(&data[glyphs_end..])
+        } else {
+            None
+        };
+        Ok(Font {
+            height,
+            glyph_count,
+            glyphs: &data[PSF1_HEADER_SIZE..glyphs_end],
+            unicode,
+        })
+    }
+
+    /// The font built into the kernel
+    pub fn default_font() -> Font {
+        Font::parse(DEFAULT_FONT).expect("Built-in font is broken")
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        GLYPH_WIDTH
+    }
+
+    /// Index of the glyph for `c`. Without a unicode table, glyphs are
+    /// assumed to be in code point order
+    pub fn glyph_index(&self, c: char) -> Option<usize> {
+        let table = match self.unicode {
+            Some(table) => table,
+            None => return Some(c as usize).filter(|&index| index < self.glyph_count),
+        };
+        // Only the basic multilingual plane fits in the table
+        if c as u32 > 0xffff {
+            return None;
+        }
+        let mut index = 0;
+        for entry in table.chunks_exact(2) {
+            match u16::from_le_bytes([entry[0], entry[1]]) {
+                PSF1_SEPARATOR => index += 1,
+                code_point if code_point as u32 == c as u32 => return Some(index),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Rows of the glyph for `c`, or of a replacement if there's none
+    pub fn glyph(&self, c: char) -> &'static [u8] {
+        let index = self
+            .glyph_index(c)
+            .or_else(|| self.glyph_index(REPLACEMENT))
+            .unwrap_or(0);
+        &self.glyphs[index * self.height..(index + 1) * self.height]
+    }
+}
+
+// Tests
+
+#[test_case]
+fn default_font_is_parsed() {
+    let font = Font::default_font();
+    assert_eq!((font.width(), font.height()), (8, 16));
+    assert_eq!(font.glyph_count, 256);
+}
+
+#[test_case]
+fn glyphs_are_in_code_page_437_order() {
+    let font = Font::default_font();
+    assert_eq!(font.glyph_index('A'), Some(0x41));
+    assert_eq!(font.glyph_index('☺'), Some(0x01));
+    assert_eq!(font.glyph_index('─'), Some(0xc4));
+    assert_eq!(font.glyph_index('✓'), Some(0xfb));
+    assert!(font.glyph('A').iter().any(|&row| row != 0));
+    assert!(font.glyph(' ').iter().all(|&row| row == 0));
+}
+
+#[test_case]
+fn missing_glyphs_are_replaced() {
+    let font = Font::default_font();
+    assert_eq!(font.glyph_index('€'), None);
+    assert_eq!(font.glyph('€'), font.glyph('■'));
+}
+
+#[test_case]
+fn box_drawing_reaches_the_cell_edges() {
+    let font = Font::default_font();
+    let vertical = font.glyph('│');
+    assert_ne!(vertical[0], 0);
+    assert_ne!(vertical[font.height() - 1], 0);
+}
+
+#[test_case]
+fn bad_fonts_are_rejected() {
+    assert_eq!(Font::parse(&[0x36]).unwrap_err(), FontError::Truncated);
+    assert_eq!(
+        Font::parse(&[0, 0, 0, 16]).unwrap_err(),
+        FontError::BadMagic
+    );
+    assert_eq!(
+        Font::parse(&[0x36, 0x04, 0, 16, 0]).unwrap_err(),
+        FontError::Truncated
+    );
+}
+
+// end of tests
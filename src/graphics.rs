@@ -0,0 +1,581 @@
+use alloc::{vec, vec::Vec};
+use core::mem;
+
+use crate::framebuffer::{font::Font, Rgb};
+
+pub mod splash;
+
+/// Dirty rectangles a surface keeps apart before merging them all into one
+const MAX_DIRTY_RECTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Smallest rectangle containing both corners
+    pub fn spanning(a: (i32, i32), b: (i32, i32)) -> Rect {
+        let (x, y) = (a.0.min(b.0), a.1.min(b.1));
+        let width = (a.0 - b.0).unsigned_abs() + 1;
+        let height = (a.1 - b.1).unsigned_abs() + 1;
+        Rect::new(x, y, width, height)
+    }
+
+    /// One past the rightmost column
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    /// One past the bottom row
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x..self.right()).contains(&x) && (self.y..self.bottom()).contains(&y)
+    }
+
+    /// Area covered by both, or `None` if they don't overlap
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        if right <= x || bottom <= y {
+            return None;
+        }
+        Some(Rect::new(x, y, (right - x) as u32, (bottom - y) as u32))
+    }
+
+    /// Smallest rectangle covering both
+    pub fn union(&self, other: &Rect) -> Rect {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let (right, bottom) = (
+            self.right().max(other.right()),
+            self.bottom().max(other.bottom()),
+        );
+        Rect::new(x, y, (right - x) as u32, (bottom - y) as u32)
+    }
+}
+
+/// Image with an alpha channel, with pixels in `0xAARRGGBB` format
+pub struct Image<'a> {
+    width: usize,
+    height: usize,
+    pixels: &'a [u32],
+}
+
+impl<'a> Image<'a> {
+    pub fn new(width: usize, height: usize, pixels: &'a [u32]) -> Image<'a> {
+        assert_eq!(pixels.len(), width * height, "Image size doesn't match");
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+/// How text is drawn on a canvas
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    pub font: Font,
+    pub color: Rgb,
+    /// Colour of the unset glyph pixels, which are left alone if `None`
+    pub background: Option<Rgb>,
+    /// Size of each glyph pixel, in pixels
+    pub scale: u32,
+}
+
+impl TextStyle {
+    pub fn new(font: Font, color: Rgb) -> TextStyle {
+        TextStyle {
+            font,
+            color,
+            background: None,
+            scale: 1,
+        }
+    }
+
+    pub fn with_background(self, background: Rgb) -> TextStyle {
+        TextStyle {
+            background: Some(background),
+            ..self
+        }
+    }
+
+    pub fn scaled(self, scale: u32) -> TextStyle {
+        TextStyle { scale, ..self }
+    }
+}
+
+/// A grid of 32 bit `0x00RRGGBB` pixels that can be drawn on. Everything
+/// drawn is clipped to the canvas
+pub trait Canvas {
+    /// Size in pixels as `(width, height)`
+    fn size(&self) -> (usize, usize);
+
+    /// Pixels of line `y`, exactly as wide as the canvas
+    fn row(&self, y: usize) -> &[u32];
+
+    fn row_mut(&mut self, y: usize) -> &mut [u32];
+
+    /// Told about the area covered by each drawing operation
+    fn mark_dirty(&mut self, _area: Rect) {}
+
+    fn bounds(&self) -> Rect {
+        let (width, height) = self.size();
+        Rect::new(0, 0, width as u32, height as u32)
+    }
+
+    /// Colour of the pixel at `(x, y)`, or `None` if it's outside the canvas
+    fn pixel(&self, x: i32, y: i32) -> Option<Rgb> {
+        if !self.bounds().contains(x, y) {
+            return None;
+        }
+        Some(Rgb::from_pixel(self.row(y as usize)[x as usize]))
+    }
+
+    fn draw_pixel(&mut self, x: i32, y: i32, color: Rgb) {
+        plot(self, x, y, color.to_pixel());
+        self.mark_dirty(Rect::new(x, y, 1, 1));
+    }
+
+    fn fill_rect(&mut self, area: Rect, color: Rgb) {
+        let pixel = color.to_pixel();
+        for y in area.y..area.bottom() {
+            fill_span(self, area.x, area.right() - 1, y, pixel);
+        }
+        self.mark_dirty(area);
+    }
+
+    /// Draw the one pixel wide outline of `area`
+    fn draw_rect(&mut self, area: Rect, color: Rgb) {
+        if area.is_empty() {
+            return;
+        }
+        let pixel = color.to_pixel();
+        let (right, bottom) = (area.right() - 1, area.bottom() - 1);
+        fill_span(self, area.x, right, area.y, pixel);
+        fill_span(self, area.x, right, bottom, pixel);
+        for y in area.y..=bottom {
+            plot(self, area.x, y, pixel);
+            plot(self, right, y, pixel);
+        }
+        self.mark_dirty(area);
+    }
+
+    /// Draw a line including both end points
+    fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), color: Rgb) {
+        // Bresenham's algorithm, for any direction
+        let pixel = color.to_pixel();
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let step_x = if x < to.0 { 1 } else { -1 };
+        let step_y = if y < to.1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            plot(self, x, y, pixel);
+            if (x, y) == to {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+        self.mark_dirty(Rect::spanning(from, to));
+    }
+
+    fn draw_circle(&mut self, center: (i32, i32), radius: u32, color: Rgb) {
+        let pixel = color.to_pixel();
+        let (cx, cy) = center;
+        for_each_octant_point(radius, |x, y| {
+            for &(px, py) in &[(x, y), (y, x), (-y, x), (-x, y)] {
+                plot(self, cx + px, cy + py, pixel);
+                plot(self, cx - px, cy - py, pixel);
+            }
+        });
+        self.mark_dirty(circle_bounds(center, radius));
+    }
+
+    fn fill_circle(&mut self, center: (i32, i32), radius: u32, color: Rgb) {
+        let pixel = color.to_pixel();
+        let (cx, cy) = center;
+        for_each_octant_point(radius, |x, y| {
+            fill_span(self, cx - x, cx + x, cy + y, pixel);
+            fill_span(self, cx - x, cx + x, cy - y, pixel);
+            fill_span(self, cx - y, cx + y, cy + x, pixel);
+            fill_span(self, cx - y, cx + y, cy - x, pixel);
+        });
+        self.mark_dirty(circle_bounds(center, radius));
+    }
+
+    /// Draw `image` with its top left corner at `(x, y)`, blending it with
+    /// what's underneath by its alpha channel
+    fn blit(&mut self, x: i32, y: i32, image: &Image) {
+        let area = Rect::new(x, y, image.width as u32, image.height as u32);
+        let visible = match area.intersection(&self.bounds()) {
+            Some(visible) => visible,
+            None => return,
+        };
+        for row in visible.y..visible.bottom() {
+            let source = &image.pixels[(row - y) as usize * image.width..];
+            let target = self.row_mut(row as usize);
+            for column in visible.x..visible.right() {
+                let pixel = source[(column - x) as usize];
+                let target = &mut target[column as usize];
+                *target = match (pixel >> 24) as u8 {
+                    0 => continue,
+                    0xff => pixel & 0x00ff_ffff,
+                    alpha => Rgb::from_pixel(*target)
+                        .blend(Rgb::from_pixel(pixel), alpha)
+                        .to_pixel(),
+                };
+            }
+        }
+        self.mark_dirty(visible);
+    }
+
+    /// Draw `c` with the top left corner of its cell at `(x, y)`
+    fn draw_char(&mut self, x: i32, y: i32, c: char, style: &TextStyle) {
+        let scale = style.scale as i32;
+        let color = style.color.to_pixel();
+        let background = style.background.map(Rgb::to_pixel);
+        for (line, bits) in style.font.glyph(c).iter().enumerate() {
+            for column in 0..style.font.width() {
+                let pixel = if bits & (0x80 >> column) != 0 {
+                    color
+                } else if let Some(background) = background {
+                    background
+                } else {
+                    continue;
+                };
+                let (px, py) = (x + column as i32 * scale, y + line as i32 * scale);
+                for row in py..py + scale {
+                    fill_span(self, px, px + scale - 1, row, pixel);
+                }
+            }
+        }
+        let (width, height) = (style.font.width() as u32, style.font.height() as u32);
+        self.mark_dirty(Rect::new(x, y, width * style.scale, height * style.scale));
+    }
+
+    /// Draw `text` on a single line starting at `(x, y)`. Returns the x
+    /// coordinate just past its end
+    fn draw_text(&mut self, x: i32, y: i32, text: &str, style: &TextStyle) -> i32 {
+        let advance = (style.font.width() as u32 * style.scale) as i32;
+        let mut x = x;
+        for c in text.chars() {
+            self.draw_char(x, y, c, style);
+            x += advance;
+        }
+        x
+    }
+}
+
+/// Set a pixel without marking it dirty, if it's on the canvas
+fn plot<C: Canvas + ?Sized>(canvas: &mut C, x: i32, y: i32, pixel: u32) {
+    if canvas.bounds().contains(x, y) {
+        canvas.row_mut(y as usize)[x as usize] = pixel;
+    }
+}
+
+/// Set pixels `x0` to `x1` inclusive on line `y`, without marking them dirty
+fn fill_span<C: Canvas + ?Sized>(canvas: &mut C, x0: i32, x1: i32, y: i32, pixel: u32) {
+    let (width, height) = canvas.size();
+    if y < 0 || y >= height as i32 || x1 < 0 || x0 >= width as i32 || x1 < x0 {
+        return;
+    }
+    let (start, end) = (x0.max(0) as usize, (x1 as usize).min(width - 1));
+    canvas.row_mut(y as usize)[start..=end].fill(pixel);
+}
+
+/// Walk the points of one octant of a circle around the origin with the
+/// midpoint algorithm, from `(radius, 0)` until x and y meet
+fn for_each_octant_point(radius: u32, mut f: impl FnMut(i32, i32)) {
+    let (mut x, mut y) = (radius as i32, 0);
+    let mut error = 1 - x;
+    while x >= y {
+        f(x, y);
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+}
+
+fn circle_bounds(center: (i32, i32), radius: u32) -> Rect {
+    let radius = radius as i32;
+    Rect::spanning(
+        (center.0 - radius, center.1 - radius),
+        (center.0 + radius, center.1 + radius),
+    )
+}
+
+/// Off screen canvas that remembers what was drawn on it since the last
+/// `flush`, so only that has to be copied to the screen
+pub struct Surface {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+    dirty: Vec<Rect>,
+}
+
+impl Surface {
+    /// Black surface, entirely dirty so the first flush copies all of it
+    pub fn new(width: usize, height: usize) -> Surface {
+        let mut surface = Surface {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            dirty: Vec::with_capacity(MAX_DIRTY_RECTS),
+        };
+        surface.invalidate();
+        surface
+    }
+
+    /// Areas drawn on since the last flush. They never overlap
+    pub fn dirty(&self) -> &[Rect] {
+        &self.dirty
+    }
+
+    /// Mark the whole surface as dirty
+    pub fn invalidate(&mut self) {
+        let bounds = self.bounds();
+        self.mark_dirty(bounds);
+    }
+
+    /// Copy the dirty areas to `target`, with the surface's top left corner
+    /// at `(x, y)`
+    pub fn flush<C: Canvas + ?Sized>(&mut self, target: &mut C, x: i32, y: i32) {
+        for area in mem::take(&mut self.dirty) {
+            let moved = Rect::new(area.x + x, area.y + y, area.width, area.height);
+            let visible = match moved.intersection(&target.bounds()) {
+                Some(visible) => visible,
+                None => continue,
+            };
+            let start = (visible.x - x) as usize;
+            let end = start + visible.width as usize;
+            for row in visible.y..visible.bottom() {
+                let source = &self.row((row - y) as usize)[start..end];
+                let target_row = target.row_mut(row as usize);
+                target_row[visible.x as usize..visible.right() as usize].copy_from_slice(source);
+            }
+            target.mark_dirty(visible);
+        }
+    }
+}
+
+impl Canvas for Surface {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn row(&self, y: usize) -> &[u32] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u32] {
+        &mut self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    fn mark_dirty(&mut self, area: Rect) {
+        let mut area = match area.intersection(&self.bounds()) {
+            Some(area) => area,
+            None => return,
+        };
+        // Merge overlapping areas so nothing gets copied twice
+        while let Some(index) = self
+            .dirty
+            .iter()
+            .position(|dirty| dirty.intersection(&area).is_some())
+        {
+            area = area.union(&self.dirty.swap_remove(index));
+        }
+        if self.dirty.len() == MAX_DIRTY_RECTS {
+            area = self
+                .dirty
+                .drain(..)
+                .fold(area, |area, dirty| area.union(&dirty));
+        }
+        self.dirty.push(area);
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+const TEST_SIZE: usize = 32;
+
+/// Canvas on the stack, since the unit tests have no heap for a `Surface`
+#[cfg(test)]
+struct TestCanvas {
+    pixels: [u32; TEST_SIZE * TEST_SIZE],
+}
+
+#[cfg(test)]
+impl TestCanvas {
+    fn new() -> TestCanvas {
+        TestCanvas {
+            pixels: [0; TEST_SIZE * TEST_SIZE],
+        }
+    }
+
+    fn count(&self, color: Rgb) -> usize {
+        self.pixels
+            .iter()
+            .filter(|&&pixel| pixel == color.to_pixel())
+            .count()
+    }
+}
+
+#[cfg(test)]
+impl Canvas for TestCanvas {
+    fn size(&self) -> (usize, usize) {
+        (TEST_SIZE, TEST_SIZE)
+    }
+
+    fn row(&self, y: usize) -> &[u32] {
+        &self.pixels[y * TEST_SIZE..(y + 1) * TEST_SIZE]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u32] {
+        &mut self.pixels[y * TEST_SIZE..(y + 1) * TEST_SIZE]
+    }
+}
+
+#[cfg(test)]
+const WHITE: Rgb = Rgb::new(0xff, 0xff, 0xff);
+
+#[test_case]
+fn rects_intersect_and_unite() {
+    let a = Rect::new(0, 0, 10, 10);
+    let b = Rect::new(5, -5, 10, 10);
+    assert_eq!(a.intersection(&b), Some(Rect::new(5, 0, 5, 5)));
+    assert_eq!(a.union(&b), Rect::new(0, -5, 15, 15));
+    assert_eq!(a.intersection(&Rect::new(10, 0, 5, 5)), None);
+    assert_eq!(Rect::spanning((3, 4), (1, 1)), Rect::new(1, 1, 3, 4));
+}
+
+#[test_case]
+fn pixels_are_clipped() {
+    let mut canvas = TestCanvas::new();
+    canvas.draw_pixel(3, 4, WHITE);
+    canvas.draw_pixel(-1, 4, WHITE);
+    canvas.draw_pixel(3, TEST_SIZE as i32, WHITE);
+    assert_eq!(canvas.pixel(3, 4), Some(WHITE));
+    assert_eq!(canvas.pixel(-1, 4), None);
+    assert_eq!(canvas.count(WHITE), 1);
+}
+
+#[test_case]
+fn rects_are_filled_and_outlined() {
+    let mut canvas = TestCanvas::new();
+    canvas.fill_rect(Rect::new(-2, -2, 4, 4), WHITE);
+    assert_eq!(canvas.count(WHITE), 4);
+
+    let mut canvas = TestCanvas::new();
+    canvas.draw_rect(Rect::new(2, 2, 4, 3), WHITE);
+    assert_eq!(canvas.count(WHITE), 10);
+    assert_eq!(canvas.pixel(3, 3), Some(Rgb::new(0, 0, 0)));
+}
+
+#[test_case]
+fn lines_include_both_ends() {
+    let mut canvas = TestCanvas::new();
+    canvas.draw_line((1, 1), (8, 4), WHITE);
+    assert_eq!(canvas.pixel(1, 1), Some(WHITE));
+    assert_eq!(canvas.pixel(8, 4), Some(WHITE));
+    assert_eq!(canvas.count(WHITE), 8);
+
+    let mut canvas = TestCanvas::new();
+    canvas.draw_line((5, 10), (5, 2), WHITE);
+    assert_eq!(canvas.count(WHITE), 9);
+}
+
+#[test_case]
+fn circles_are_symmetric() {
+    let mut canvas = TestCanvas::new();
+    canvas.draw_circle((8, 8), 5, WHITE);
+    for &(x, y) in &[(13, 8), (3, 8), (8, 13), (8, 3)] {
+        assert_eq!(canvas.pixel(x, y), Some(WHITE));
+    }
+    assert_eq!(canvas.pixel(8, 8), Some(Rgb::new(0, 0, 0)));
+
+    canvas.fill_circle((8, 8), 5, WHITE);
+    assert_eq!(canvas.pixel(8, 8), Some(WHITE));
+    assert_eq!(canvas.pixel(12, 12), Some(Rgb::new(0, 0, 0)));
+}
+
+#[test_case]
+fn images_are_blended_and_clipped() {
+    let mut canvas = TestCanvas::new();
+    canvas.fill_rect(canvas.bounds(), Rgb::new(0, 0, 0xff));
+    #[rustfmt::skip]
+    let pixels = [
+        0xffff_0000, 0x0000_ff00,
+        0x8000_ff00, 0xff00_00ff,
+    ];
+    let image = Image::new(2, 2, &pixels);
+    canvas.blit(TEST_SIZE as i32 - 1, 0, &image);
+    canvas.blit(0, 0, &image);
+    assert_eq!(canvas.pixel(0, 0), Some(Rgb::new(0xff, 0, 0)));
+    assert_eq!(canvas.pixel(1, 0), Some(Rgb::new(0, 0, 0xff)));
+    assert_eq!(canvas.pixel(0, 1), Some(Rgb::new(0, 0x80, 0x7f)));
+    assert_eq!(
+        canvas.pixel(TEST_SIZE as i32 - 1, 0),
+        Some(Rgb::new(0xff, 0, 0))
+    );
+}
+
+#[test_case]
+fn text_is_drawn() {
+    let mut canvas = TestCanvas::new();
+    let style = TextStyle::new(Font::default_font(), WHITE);
+    let end = canvas.draw_text(0, 0, "||", &style);
+    assert_eq!(end, 16);
+    let lit = canvas.count(WHITE);
+    assert!(lit > 0);
+
+    let mut canvas = TestCanvas::new();
+    canvas.draw_text(0, 0, "|", &style.scaled(2));
+    assert_eq!(canvas.count(WHITE), lit / 2 * 4);
+}
+
+// end of tests
@@ -0,0 +1,132 @@
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::{Canvas, Rect, TextStyle};
+use crate::{
+    console::{self, Backend},
+    framebuffer::{font::Font, Rgb, FRAMEBUFFER},
+};
+
+/// Height of the splash in pixels. It spans the whole screen width
+pub const HEIGHT: usize = 160;
+
+/// The goat, one character per pixel
+#[rustfmt::skip]
+const GOAT: [&str; 14] = [
+    "h............h..",
+    "hh..........hh..",
+    ".hh........hh...",
+    "..hhwwwwwwhh....",
+    "...wwwwwwwww....",
+    "..wwewwwwewww...",
+    "..wwwwwwwwwww...",
+    "gwwwwwwwwwwwwg..",
+    "...wwwwwwwww....",
+    "....wwwwwww.....",
+    "....wwwpwww.....",
+    ".....wwwww......",
+    "......ggg.......",
+    "......g.g.......",
+];
+/// Size of each goat pixel on screen
+const GOAT_SCALE: usize = 6;
+
+const TITLE: &str = "Blight OS";
+const TAGLINE: &str = "bah!";
+const TITLE_COLOR: Rgb = Rgb::new(0x55, 0xff, 0x55);
+const TAGLINE_COLOR: Rgb = Rgb::new(0xaa, 0xaa, 0xaa);
+const TOP_COLOR: Rgb = Rgb::new(0x30, 0x10, 0x40);
+const BOTTOM_COLOR: Rgb = Rgb::new(0x00, 0x00, 0x00);
+
+fn goat_color(c: char) -> Option<Rgb> {
+    match c {
+        'w' => Some(Rgb::new(0xee, 0xee, 0xee)),
+        'g' => Some(Rgb::new(0xaa, 0xaa, 0xaa)),
+        'h' => Some(Rgb::new(0x8b, 0x5a, 0x2b)),
+        'e' => Some(Rgb::new(0x00, 0x00, 0x00)),
+        'p' => Some(Rgb::new(0xff, 0x99, 0xaa)),
+        _ => None,
+    }
+}
+
+/// Draw the goat scaled up by `GOAT_SCALE`, with its top left corner at
+/// `(x, y)`
+fn draw_goat(canvas: &mut impl Canvas, x: i32, y: i32) {
+    for (row, line) in GOAT.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            if let Some(color) = goat_color(c) {
+                let scale = GOAT_SCALE as i32;
+                let area = Rect::new(
+                    x + column as i32 * scale,
+                    y + row as i32 * scale,
+                    GOAT_SCALE as u32,
+                    GOAT_SCALE as u32,
+                );
+                canvas.fill_rect(area, color);
+            }
+        }
+    }
+}
+
+/// Draw the splash across the top `HEIGHT` pixels of a canvas. Drawn in
+/// place, without allocating
+pub fn draw(canvas: &mut impl Canvas) {
+    let (width, height) = canvas.size();
+    let height = height.min(HEIGHT);
+    for y in 0..height {
+        let alpha = (y * 255 / height) as u8;
+        let color = TOP_COLOR.blend(BOTTOM_COLOR, alpha);
+        canvas.fill_rect(Rect::new(0, y as i32, width as u32, 1), color);
+    }
+    canvas.draw_rect(
+        Rect::new(4, 4, width as u32 - 8, height as u32 - 8),
+        TAGLINE_COLOR,
+    );
+
+    let goat_width = GOAT[0].len() * GOAT_SCALE;
+    let goat_height = GOAT.len() * GOAT_SCALE;
+    draw_goat(canvas, 40, (height as i32 - goat_height as i32) / 2);
+
+    let font = Font::default_font();
+    let title = TextStyle::new(font, TITLE_COLOR).scaled(4);
+    let title_x = 40 + goat_width as i32 + 32;
+    let title_end = canvas.draw_text(title_x, 24, TITLE, &title);
+    let underline_y = 24 + (font.height() * 4) as i32 + 4;
+    canvas.draw_line(
+        (title_x, underline_y),
+        (title_end, underline_y),
+        TITLE_COLOR,
+    );
+    let tagline = TextStyle::new(font, TAGLINE_COLOR).scaled(2);
+    canvas.draw_text(title_x, underline_y + 12, TAGLINE, &tagline);
+
+    let moon = (width as i32 - 90, height as i32 / 2);
+    canvas.fill_circle(moon, 48, Rgb::new(0x50, 0x40, 0x60));
+    canvas.draw_circle(moon, 56, TAGLINE_COLOR);
+}
+
+/// Draw the splash across the top of the framebuffer console and move the
+/// console below it. Returns false if the console isn't on the framebuffer
+pub fn show() -> bool {
+    let framebuffer = match FRAMEBUFFER.get() {
+        Some(framebuffer) if console::backend() == Backend::Framebuffer => framebuffer,
+        _ => return false,
+    };
+    let rows = (HEIGHT + Font::default_font().height() - 1) / Font::default_font().height();
+    let console = console::screen();
+    console.clear();
+    // Moved first, so the cursor isn't drawn over the splash
+    console.set_position(rows, 0);
+    without_interrupts(|| draw(&mut *framebuffer.lock()));
+    true
+}
+
+// Tests
+
+#[test_case]
+fn goat_is_rectangular() {
+    for line in GOAT.iter() {
+        assert_eq!(line.len(), GOAT[0].len());
+    }
+}
+
+// end of tests
@@ -0,0 +1,140 @@
+use core::{
+    mem::size_of,
+    ptr::{read_unaligned, read_volatile, write_volatile},
+};
+
+use conquer_once::spin::OnceCell;
+use x86_64::{
+    structures::paging::{mapper::MapToError, FrameAllocator, Mapper, Size4KiB},
+    PhysAddr, VirtAddr,
+};
+
+use crate::{
+    acpi::{self, AcpiError, SdtHeader},
+    memory,
+};
+
+// Register offsets
+const CAPABILITIES: usize = 0x00;
+const CONFIGURATION: usize = 0x10;
+const MAIN_COUNTER: usize = 0xf0;
+
+const CONFIGURATION_ENABLE: u64 = 1 << 0;
+
+/// Counter period is reported in femtoseconds
+const FEMTOSECONDS_PER_SECOND: u64 = 1_000_000_000_000_000;
+/// Longest counter period the specification allows, 100ns
+const MAX_PERIOD_FS: u64 = 0x05f5_e100;
+
+/// ACPI generic address space the registers are in
+const ADDRESS_SPACE_MEMORY: u8 = 0;
+
+#[derive(Debug)]
+pub enum HpetError {
+    Acpi(AcpiError),
+    /// The registers aren't memory mapped, but in the given address space
+    UnsupportedAddressSpace(u8),
+    /// The counter period, in femtoseconds, is zero or longer than 100ns
+    InvalidPeriod(u64),
+    AlreadyInitialised,
+    MappingFailed(MapToError<Size4KiB>),
+}
+
+impl From<AcpiError> for HpetError {
+    fn from(err: AcpiError) -> Self {
+        HpetError::Acpi(err)
+    }
+}
+
+impl From<MapToError<Size4KiB>> for HpetError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        HpetError::MappingFailed(err)
+    }
+}
+
+/// Body of the ACPI HPET table, following the common header
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct HpetTable {
+    event_timer_block_id: u32,
+    address_space_id: u8,
+    register_bit_width: u8,
+    register_bit_offset: u8,
+    reserved: u8,
+    address: u64,
+    hpet_number: u8,
+    minimum_tick: u16,
+    page_protection: u8,
+}
+
+pub struct Hpet {
+    base: VirtAddr,
+    period_fs: u64,
+}
+
+impl Hpet {
+    unsafe fn read(&self, register: usize) -> u64 {
+        read_volatile((self.base + register).as_ptr())
+    }
+
+    unsafe fn write(&self, register: usize, value: u64) {
+        write_volatile((self.base + register).as_mut_ptr(), value)
+    }
+
+    /// Current value of the main counter
+    pub fn counter(&self) -> u64 {
+        unsafe { self.read(MAIN_COUNTER) }
+    }
+
+    /// Frequency of the main counter in Hz
+    pub fn frequency(&self) -> u64 {
+        FEMTOSECONDS_PER_SECOND / self.period_fs
+    }
+
+    /// Busy wait until `ns` nanoseconds have passed
+    pub fn sleep_ns(&self, ns: u64) {
+        let ticks = (ns as u128 * 1_000_000 / self.period_fs as u128) as u64;
+        let start = self.counter();
+        while self.counter().wrapping_sub(start) < ticks {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+static HPET: OnceCell<Hpet> = OnceCell::uninit();
+
+/// Locate the HPET through ACPI, map its registers and start the main counter
+pub fn init(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), HpetError> {
+    if HPET.is_initialized() {
+        return Err(HpetError::AlreadyInitialised);
+    }
+    let table_address = acpi::find_table(b"HPET")?;
+    let table = unsafe {
+        let body = table_address + size_of::<SdtHeader>();
+        read_unaligned(memory::phys_to_virt(body).as_ptr::<HpetTable>())
+    };
+    if table.address_space_id != ADDRESS_SPACE_MEMORY {
+        return Err(HpetError::UnsupportedAddressSpace(table.address_space_id));
+    }
+    let base = memory::map_mmio(PhysAddr::new(table.address), 0x400, mapper, frame_allocator)?;
+
+    let mut hpet = Hpet { base, period_fs: 0 };
+    hpet.period_fs = unsafe { hpet.read(CAPABILITIES) } >> 32;
+    if hpet.period_fs == 0 || hpet.period_fs > MAX_PERIOD_FS {
+        return Err(HpetError::InvalidPeriod(hpet.period_fs));
+    }
+    unsafe {
+        let configuration = hpet.read(CONFIGURATION);
+        hpet.write(CONFIGURATION, configuration | CONFIGURATION_ENABLE);
+    }
+    HPET.try_init_once(|| hpet)
+        .map_err(|_| HpetError::AlreadyInitialised)
+}
+
+/// The HPET, if it has been initialized
+pub fn get() -> Option<&'static Hpet> {
+    HPET.try_get().ok()
+}
@@ -1,39 +1,318 @@
-use core::convert::TryInto;
+use core::fmt;
 use core::panic;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use lazy_static::lazy_static;
 use pic8259::ChainedPics;
 use spin;
 use x86_64::instructions::port::Port;
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+use x86_64::structures::idt::{
+    HandlerFunc, InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode,
+};
 
-use crate::keyboard::{decode, KeyboardEvent};
-use crate::print;
-use crate::println;
-use crate::{gdt, hlt_loop};
+use crate::emergency_println;
+use crate::{apic, gdt, hlt_loop, serial};
 
 pub const PIC1_OFFSET: u8 = 32;
 pub const PIC2_OFFSET: u8 = PIC1_OFFSET + 8;
 
+/// Number of legacy ISA IRQ lines
+pub const IRQ_COUNT: usize = 16;
+
+/// How many handlers can share a single IRQ line
+pub const MAX_SHARED_HANDLERS: usize = 4;
+
 pub static PICS: spin::Mutex<ChainedPics> =
     spin::Mutex::new(unsafe { ChainedPics::new(PIC1_OFFSET, PIC2_OFFSET) });
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
-enum InterruptIndex {
+pub enum InterruptIndex {
     Timer = PIC1_OFFSET,
-    Keyboard = PIC1_OFFSET + 1,
+    ApicSpurious = apic::SPURIOUS_VECTOR,
 }
 
 impl InterruptIndex {
-    fn as_u8(self) -> u8 {
+    pub fn as_u8(self) -> u8 {
         self as u8
     }
 
-    fn as_usize(self) -> usize {
+    pub fn as_usize(self) -> usize {
         self as usize
     }
 }
 
+/// Conventional assignment of the ISA IRQ lines
+#[rustfmt::skip]
+const IRQ_NAMES: [&str; IRQ_COUNT] = [
+    "timer", "keyboard", "cascade", "COM2", "COM1", "LPT2", "floppy", "LPT1",
+    "RTC", "ACPI", "IRQ10", "IRQ11", "mouse", "FPU", "ATA primary", "ATA secondary",
+];
+
+#[rustfmt::skip]
+const EXCEPTION_NAMES: [&str; 32] = [
+    "divide error", "debug", "NMI", "breakpoint", "overflow", "bound range",
+    "invalid opcode", "device not available", "double fault", "coprocessor overrun",
+    "invalid TSS", "segment not present", "stack segment", "general protection",
+    "page fault", "reserved", "x87 FPU", "alignment check", "machine check", "SIMD",
+    "virtualization", "control protection", "reserved", "reserved", "reserved",
+    "reserved", "reserved", "reserved", "hypervisor injection", "VMM communication",
+    "security", "reserved",
+];
+
+// Interrupt counts, indexed by vector
+const ZERO: AtomicU64 = AtomicU64::new(0);
+static INTERRUPT_COUNTS: [AtomicU64; 256] = [ZERO; 256];
+/// Interrupts that arrived on an IRQ line with no handler registered
+static UNHANDLED_COUNT: AtomicU64 = AtomicU64::new(0);
+/// Spurious IRQ7/IRQ15 raised by the PICs
+static SPURIOUS_COUNT: AtomicU64 = AtomicU64::new(0);
+/// Interrupts on vectors that have no handler in the IDT at all
+static UNKNOWN_VECTOR_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// How many interrupt handlers each CPU is nested in
+const NOT_NESTED: AtomicUsize = AtomicUsize::new(0);
+static INTERRUPT_DEPTH: [AtomicUsize; apic::MAX_CPUS] = [NOT_NESTED; apic::MAX_CPUS];
+
+/// Marks the CPU as running an interrupt handler until it's dropped
+struct InterruptContext {
+    cpu: usize,
+}
+
+impl InterruptContext {
+    fn enter() -> InterruptContext {
+        let cpu = apic::cpu_index();
+        INTERRUPT_DEPTH[cpu].fetch_add(1, Ordering::Relaxed);
+        InterruptContext { cpu }
+    }
+}
+
+impl Drop for InterruptContext {
+    fn drop(&mut self) {
+        INTERRUPT_DEPTH[self.cpu].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Whether this is running inside an interrupt or exception handler, where
+/// taking a lock the interrupted code might hold would deadlock
+pub fn in_interrupt() -> bool {
+    INTERRUPT_DEPTH[apic::cpu_index()].load(Ordering::Relaxed) > 0
+}
+
+fn count_interrupt(vector: u8) {
+    INTERRUPT_COUNTS[vector as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of times the interrupt on `vector` has been received
+pub fn interrupt_count(vector: u8) -> u64 {
+    INTERRUPT_COUNTS[vector as usize].load(Ordering::Relaxed)
+}
+
+fn vector_name(vector: u8) -> &'static str {
+    match vector {
+        0..=31 => EXCEPTION_NAMES[vector as usize],
+        v if v >= PIC1_OFFSET && v < PIC1_OFFSET + IRQ_COUNT as u8 => {
+            IRQ_NAMES[(v - PIC1_OFFSET) as usize]
+        }
+        apic::SPURIOUS_VECTOR => "APIC spurious",
+        _ => "",
+    }
+}
+
+/// Print a table of every vector that has fired, or has a handler registered,
+/// along with how many times it fired, to the serial port. Similar to
+/// `/proc/interrupts`
+pub fn print_interrupt_table() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        write_interrupt_table(&mut *serial::SERIAL1.lock()).expect("Failed writing to SERIAL1");
+    });
+}
+
+/// Write the table `print_interrupt_table` prints to `out`
+pub fn write_interrupt_table(out: &mut impl fmt::Write) -> fmt::Result {
+    let registered = registered_irqs().fold(0u16, |mask, irq| mask | 1 << irq);
+    writeln!(out, "{:>6}  {:<24}{:>12}", "VECTOR", "NAME", "COUNT")?;
+    for vector in 0..=255u8 {
+        let count = interrupt_count(vector);
+        let is_registered = vector >= PIC1_OFFSET
+            && vector < PIC1_OFFSET + IRQ_COUNT as u8
+            && registered & 1 << (vector - PIC1_OFFSET) != 0;
+        if count > 0 || is_registered {
+            writeln!(
+                out,
+                "{:>6}  {:<24}{:>12}",
+                vector,
+                vector_name(vector),
+                count
+            )?;
+        }
+    }
+    let totals = [
+        ("unhandled IRQ", &UNHANDLED_COUNT),
+        ("spurious IRQ", &SPURIOUS_COUNT),
+        ("unknown vector", &UNKNOWN_VECTOR_COUNT),
+    ];
+    for (name, count) in totals.iter() {
+        writeln!(
+            out,
+            "{:>6}  {:<24}{:>12}",
+            "-",
+            name,
+            count.load(Ordering::Relaxed)
+        )?;
+    }
+    Ok(())
+}
+
+/// Handler for a device IRQ. Gets called with the IRQ number it fired on
+pub type IrqHandler = fn(irq: u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    InvalidIrq(u8),
+    /// All handler slots on the line are taken
+    LineFull(u8),
+}
+
+static IRQ_HANDLERS: spin::Mutex<[[Option<IrqHandler>; MAX_SHARED_HANDLERS]; IRQ_COUNT]> =
+    spin::Mutex::new([[None; MAX_SHARED_HANDLERS]; IRQ_COUNT]);
+
+/// Install `handler` on `irq` and unmask the line on the active interrupt
+/// controller.
+///
+/// Lines can be shared, in which case every handler on the line is invoked
+/// each time it fires, in the order they were registered.
+pub fn register_irq(irq: u8, handler: IrqHandler) -> Result<(), IrqError> {
+    if irq as usize >= IRQ_COUNT {
+        return Err(IrqError::InvalidIrq(irq));
+    }
+    // The handler table is also locked from the dispatch stubs, so it must
+    // never be held with interrupts enabled
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut handlers = IRQ_HANDLERS.lock();
+        let slot = handlers[irq as usize]
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(IrqError::LineFull(irq))?;
+        *slot = Some(handler);
+        enable_irq(irq);
+        Ok(())
+    })
+}
+
+/// IRQ lines that have at least one handler installed
+pub(crate) fn registered_irqs() -> impl Iterator<Item = u8> {
+    let handlers = x86_64::instructions::interrupts::without_interrupts(|| *IRQ_HANDLERS.lock());
+    (0..IRQ_COUNT as u8).filter(move |irq| handlers[*irq as usize].iter().any(Option::is_some))
+}
+
+/// Vector an IRQ is delivered on, for both the PICs and the APIC
+pub fn irq_vector(irq: u8) -> u8 {
+    PIC1_OFFSET + irq
+}
+
+/// Remap the PICs and mask every line. Lines get unmasked as handlers are
+/// registered
+pub fn init_pics() {
+    unsafe {
+        PICS.lock().initialize();
+        Port::<u8>::new(0x21).write(0xff);
+        Port::<u8>::new(0xa1).write(0xff);
+    }
+}
+
+fn enable_irq(irq: u8) {
+    if apic::is_enabled() {
+        apic::enable_irq(irq);
+        return;
+    }
+    let (port, line) = if irq < 8 {
+        (0x21, irq)
+    } else {
+        (0xa1, irq - 8)
+    };
+    unsafe {
+        let mut mask = Port::<u8>::new(port);
+        let current = mask.read();
+        mask.write(current & !(1 << line));
+        // Lines on the secondary PIC also need the cascade line unmasked
+        if irq >= 8 {
+            let mut primary = Port::<u8>::new(0x21);
+            let current = primary.read();
+            primary.write(current & !(1 << 2));
+        }
+    }
+}
+
+/// Signal end of interrupt to whichever interrupt controller is in use
+fn notify_end_of_interrupt(irq: u8) {
+    if apic::is_enabled() {
+        apic::end_of_interrupt();
+    } else {
+        unsafe { PICS.lock().notify_end_of_interrupt(irq_vector(irq)) };
+    }
+}
+
+/// Read the In-Service Register of the PIC at `command_port`
+fn read_pic_isr(command_port: u16) -> u8 {
+    let mut port = Port::<u8>::new(command_port);
+    unsafe {
+        // OCW3: read ISR on next read
+        port.write(0x0b);
+        port.read()
+    }
+}
+
+/// The PICs raise IRQ7 (or IRQ15 on the secondary) when an interrupt goes
+/// away before it's acknowledged. Those don't have their ISR bit set, and must
+/// not get an EOI, except the primary PIC must still get one for the cascade
+/// when the spurious IRQ came from the secondary
+fn is_spurious_irq(irq: u8) -> bool {
+    if apic::is_enabled() {
+        return false;
+    }
+    match irq {
+        7 => read_pic_isr(0x20) & 1 << 7 == 0,
+        15 if read_pic_isr(0xa0) & 1 << 7 == 0 => {
+            unsafe { Port::<u8>::new(0x20).write(0x20) };
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Common entry point for all IRQs. Runs every handler registered on the line
+/// and acknowledges the interrupt
+fn dispatch_irq(irq: u8) {
+    if is_spurious_irq(irq) {
+        SPURIOUS_COUNT.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    count_interrupt(irq_vector(irq));
+
+    // Copy the handlers out, so handlers are free to register others
+    let handlers = IRQ_HANDLERS.lock()[irq as usize];
+    if handlers.iter().all(Option::is_none) {
+        UNHANDLED_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    for handler in handlers.iter().flatten() {
+        handler(irq);
+    }
+    notify_end_of_interrupt(irq);
+}
+
+extern "x86-interrupt" fn irq_stub<const IRQ: u8>(_stack_frame: InterruptStackFrame) {
+    let _context = InterruptContext::enter();
+    dispatch_irq(IRQ);
+}
+
+#[rustfmt::skip]
+const IRQ_STUBS: [HandlerFunc; IRQ_COUNT] = [
+    irq_stub::<0>,  irq_stub::<1>,  irq_stub::<2>,  irq_stub::<3>,
+    irq_stub::<4>,  irq_stub::<5>,  irq_stub::<6>,  irq_stub::<7>,
+    irq_stub::<8>,  irq_stub::<9>,  irq_stub::<10>, irq_stub::<11>,
+    irq_stub::<12>, irq_stub::<13>, irq_stub::<14>, irq_stub::<15>,
+];
+
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
@@ -44,8 +323,13 @@ lazy_static! {
                 .set_handler_fn(double_fault_handler)
                 .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
         }
-        idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
-        idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        for vector in irq_vector(IRQ_COUNT as u8)..apic::SPURIOUS_VECTOR {
+            idt[vector as usize].set_handler_fn(unknown_vector_handler);
+        }
+        for (irq, stub) in IRQ_STUBS.iter().enumerate() {
+            idt[irq_vector(irq as u8) as usize].set_handler_fn(*stub);
+        }
+        idt[InterruptIndex::ApicSpurious.as_usize()].set_handler_fn(spurious_interrupt_handler);
         idt
     };
 }
@@ -56,17 +340,22 @@ pub fn init_descriptor_table() {
 
 /// Breakpoint interrupt handler
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    println!("EXCEPTION: breakpoint\n{:#?}", stack_frame);
+    let _context = InterruptContext::enter();
+    count_interrupt(3);
+    log::info!("Breakpoint at {:?}", stack_frame.instruction_pointer);
 }
 
 extern "x86-interrupt" fn page_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
+    let _context = InterruptContext::enter();
+    count_interrupt(14);
     let add = x86_64::registers::control::Cr2::read();
-    println!("Tried to read address: {:?}", add);
-    println!("Error: {:?}", error_code);
-    println!("EXCEPTION: PAGE FAULT\n{:#?}", stack_frame);
+    // Never returns, so whatever the faulting code had locked stays locked
+    emergency_println!("Tried to read address: {:?}", add);
+    emergency_println!("Error: {:?}", error_code);
+    emergency_println!("EXCEPTION: PAGE FAULT\n{:#?}", stack_frame);
 
     hlt_loop();
 }
@@ -76,32 +365,55 @@ extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
     _error_code: u64,
 ) -> ! {
+    let _context = InterruptContext::enter();
+    count_interrupt(8);
     panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
 }
 
-/// Keyboard interrupt handler
-extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    let mut port = Port::new(0x60);
-    let scancode: u8 = unsafe { port.read() };
-    // let key: Result<char, ()> = match decode(scancode) {
-    //     Some(KeyboardEvent::Make(key)) => key.try_into(),
-    //     _ => Err(()),
-    // };
-    // if let Ok(character) = key {
-    //     print!("{}", character);
-    // }
-    crate::task::keyboard::add_scancode(scancode);
-    unsafe {
-        PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+/// Spurious interrupts from the local APIC must not be acknowledged
+extern "x86-interrupt" fn spurious_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    let _context = InterruptContext::enter();
+    count_interrupt(apic::SPURIOUS_VECTOR);
+}
+
+/// Catch-all for vectors nothing is expected to arrive on
+extern "x86-interrupt" fn unknown_vector_handler(_stack_frame: InterruptStackFrame) {
+    let _context = InterruptContext::enter();
+    UNKNOWN_VECTOR_COUNT.fetch_add(1, Ordering::Relaxed);
+    // Harmless if it was a software interrupt, but keeps the local APIC from
+    // blocking lower priority interrupts if something was misrouted
+    if apic::is_enabled() {
+        apic::end_of_interrupt();
     }
 }
 
-/// Timer interrupt handler
-extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    //print!(".");
-    unsafe {
-        PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+// Tests
+
+#[test_case]
+fn breakpoint_is_counted() {
+    let before = interrupt_count(3);
+    x86_64::instructions::interrupts::int3();
+    assert_eq!(interrupt_count(3), before + 1);
+}
+
+#[test_case]
+fn interrupt_context_is_tracked() {
+    assert!(!in_interrupt());
+    {
+        let _context = InterruptContext::enter();
+        assert!(in_interrupt());
     }
+    assert!(!in_interrupt());
 }
+
+#[test_case]
+fn timer_is_counted() {
+    let before = interrupt_count(InterruptIndex::Timer.as_u8());
+    // Other interrupts may wake us up too, but a few rounds is plenty at 100Hz
+    for _ in 0..5 {
+        x86_64::instructions::hlt();
+    }
+    assert!(interrupt_count(InterruptIndex::Timer.as_u8()) > before);
+}
+
+// end of tests
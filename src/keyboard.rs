@@ -3,81 +3,252 @@ use core::convert::TryInto;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-pub fn decode(scancode: u8) -> Option<KeyboardEvent> {
-    if let Some(key) = FromPrimitive::from_u8(scancode) {
-        return Some(KeyboardEvent::Make(key));
+use self::layout::Layout;
+
+pub mod layout;
+
+/// Scancode prefixing the extended keys
+const EXTENDED_PREFIX: u8 = 0xe0;
+/// Scancode starting the Pause sequence, `0xe1 0x1d 0x45 0xe1 0x9d 0xc5`
+const PAUSE_PREFIX: u8 = 0xe1;
+/// Number of bytes following `PAUSE_PREFIX` in the Pause sequence
+const PAUSE_SEQUENCE_LENGTH: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Start,
+    Extended,
+    /// Number of bytes of the Pause sequence seen so far
+    Pause(u8),
+}
+
+/// Decoder for scancode set 1, including the multi-byte sequences. Keeps track
+/// of partial sequences across calls
+pub struct ScancodeDecoder {
+    state: DecodeState,
+}
+
+impl ScancodeDecoder {
+    pub const fn new() -> Self {
+        Self {
+            state: DecodeState::Start,
+        }
     }
 
-    // break keys seems to just be corresponding make, offset by 128
-    if let Some(code) = scancode.checked_add(0x80) {
-        if let Some(key) = FromPrimitive::from_u8(code) {
-            Some(KeyboardEvent::Break(key));
+    /// Feed a single byte to the decoder. Returns an event once a complete
+    /// sequence has been received
+    pub fn decode(&mut self, scancode: u8) -> Option<KeyboardEvent> {
+        match self.state {
+            DecodeState::Start => match scancode {
+                EXTENDED_PREFIX => {
+                    self.state = DecodeState::Extended;
+                    None
+                }
+                PAUSE_PREFIX => {
+                    self.state = DecodeState::Pause(0);
+                    None
+                }
+                code => Self::event(code & 0x7f, code),
+            },
+            DecodeState::Extended => {
+                self.state = DecodeState::Start;
+                match scancode & 0x7f {
+                    // Print Screen (among others) is wrapped in a fake shift
+                    // press and release, which are ignored
+                    0x2a | 0x36 => None,
+                    // Extended keys are numbered from 0x80 in `Key`
+                    code => Self::event(code | 0x80, scancode),
+                }
+            }
+            DecodeState::Pause(seen) if seen + 1 < PAUSE_SEQUENCE_LENGTH => {
+                self.state = DecodeState::Pause(seen + 1);
+                None
+            }
+            // Pause only has a make sequence
+            DecodeState::Pause(_) => {
+                self.state = DecodeState::Start;
+                Some(KeyboardEvent::Make(Key::Pause))
+            }
         }
     }
 
-    None
+    /// Build the event for `key_code`. Break codes are the make code with the
+    /// high bit set
+    fn event(key_code: u8, scancode: u8) -> Option<KeyboardEvent> {
+        let key = FromPrimitive::from_u8(key_code)?;
+        if scancode & 0x80 == 0 {
+            Some(KeyboardEvent::Make(key))
+        } else {
+            Some(KeyboardEvent::Break(key))
+        }
+    }
 }
 
+impl Default for ScancodeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyboardEvent {
     Make(Key),  // Press
     Break(Key), // Release
 }
 
+impl KeyboardEvent {
+    /// The physical key that was pressed or released
+    pub fn key(&self) -> Key {
+        match *self {
+            KeyboardEvent::Make(key) | KeyboardEvent::Break(key) => key,
+        }
+    }
+
+    pub fn state(&self) -> KeyState {
+        match self {
+            KeyboardEvent::Make(_) => KeyState::Down,
+            KeyboardEvent::Break(_) => KeyState::Up,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Down,
+    Up,
+}
+
+/// State of the modifier and lock keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub alt: bool,
+    pub right_alt: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.left_ctrl || self.right_ctrl
+    }
+
+    /// Right Alt is AltGr. Ctrl+Alt also acts as AltGr, for keyboards without a
+    /// right Alt key
+    pub fn alt_gr(&self) -> bool {
+        self.right_alt || (self.ctrl() && self.alt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub state: KeyState,
+    /// Modifier state after this event was applied
+    pub modifiers: Modifiers,
+}
+
+/// Stateful keyboard decoder, tracking modifier and lock keys across
+/// scancodes and translating keys to characters through a `Layout`
+pub struct Keyboard {
+    decoder: ScancodeDecoder,
+    modifiers: Modifiers,
+    layout: &'static Layout,
+    // Lock keys only toggle on the initial press, not on typematic repeats
+    held_locks: [bool; 3],
+}
+
+impl Keyboard {
+    pub fn new(layout: &'static Layout) -> Self {
+        Self {
+            decoder: ScancodeDecoder::new(),
+            modifiers: Modifiers::default(),
+            layout,
+            held_locks: [false; 3],
+        }
+    }
+
+    pub fn layout(&self) -> &'static Layout {
+        self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: &'static Layout) {
+        self.layout = layout;
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// Decode a single scancode, updating the modifier state. Returns `None`
+    /// while in the middle of a multi-byte sequence
+    pub fn process_scancode(&mut self, scancode: u8) -> Option<KeyEvent> {
+        let event = self.decoder.decode(scancode)?;
+        let (key, state) = (event.key(), event.state());
+        let down = state == KeyState::Down;
+        match key {
+            Key::LeftShift => self.modifiers.left_shift = down,
+            Key::RightShift => self.modifiers.right_shift = down,
+            Key::LeftControl => self.modifiers.left_ctrl = down,
+            Key::RightControl => self.modifiers.right_ctrl = down,
+            Key::LeftAlt => self.modifiers.alt = down,
+            Key::RightAlt => self.modifiers.right_alt = down,
+            Key::CapsLock => self.toggle_lock(0, down),
+            Key::NumLock => self.toggle_lock(1, down),
+            Key::ScrollLock => self.toggle_lock(2, down),
+            _ => {}
+        }
+        Some(KeyEvent {
+            key,
+            state,
+            modifiers: self.modifiers,
+        })
+    }
+
+    fn toggle_lock(&mut self, lock: usize, down: bool) {
+        if down && !self.held_locks[lock] {
+            let state = match lock {
+                0 => &mut self.modifiers.caps_lock,
+                1 => &mut self.modifiers.num_lock,
+                _ => &mut self.modifiers.scroll_lock,
+            };
+            *state = !*state;
+        }
+        self.held_locks[lock] = down;
+    }
+
+    /// The character produced by `event` in the current layout, if any.
+    /// Only key presses produce characters
+    pub fn translate(&self, event: &KeyEvent) -> Option<char> {
+        match event.state {
+            KeyState::Down => self.layout.map(event.key, &event.modifiers),
+            KeyState::Up => None,
+        }
+    }
+
+    /// Decode a scancode and translate it to a character in one go
+    pub fn process_char(&mut self, scancode: u8) -> Option<char> {
+        let event = self.process_scancode(scancode)?;
+        self.translate(&event)
+    }
+}
+
 impl TryInto<char> for Key {
     type Error = ();
-    #[rustfmt::skip]
     fn try_into(self) -> Result<char, ()> {
-        match self {
-            Self::Num0       => Ok('0'),
-            Self::Num1       => Ok('1'),
-            Self::Num2       => Ok('2'),
-            Self::Num3       => Ok('3'),
-            Self::Num4       => Ok('4'),
-            Self::Num5       => Ok('5'),
-            Self::Num6       => Ok('6'),
-            Self::Num7       => Ok('7'),
-            Self::Num8       => Ok('8'),
-            Self::Num9       => Ok('9'),
-            Self::A          => Ok('a'),
-            Self::B          => Ok('b'),
-            Self::C          => Ok('c'),
-            Self::D          => Ok('d'),
-            Self::E          => Ok('e'),
-            Self::F          => Ok('f'),
-            Self::G          => Ok('g'),
-            Self::H          => Ok('h'),
-            Self::I          => Ok('i'),
-            Self::J          => Ok('j'),
-            Self::K          => Ok('k'),
-            Self::L          => Ok('l'),
-            Self::M          => Ok('m'),
-            Self::N          => Ok('n'),
-            Self::O          => Ok('o'),
-            Self::P          => Ok('p'),
-            Self::Q          => Ok('q'),
-            Self::R          => Ok('r'),
-            Self::S          => Ok('s'),
-            Self::T          => Ok('t'),
-            Self::U          => Ok('u'),
-            Self::V          => Ok('v'),
-            Self::W          => Ok('w'),
-            Self::X          => Ok('x'),
-            Self::Y          => Ok('y'),
-            Self::Z          => Ok('z'),
-            Self::Minus      => Ok('-'),
-            Self::Dot        => Ok('.'),
-            Self::Comma      => Ok(','),
-            Self::SemiColon  => Ok(';'),
-            Self::Apostrophe => Ok('\''),
-            Self::Tab        => Ok('\t'),
-            Self::Enter      => Ok('\n'),
-            Self::Spacebar   => Ok(' '),
-            _                => Err(()),
-        }
+        layout::US.map(self, &Modifiers::default()).ok_or(())
     }
 }
 
-#[derive(FromPrimitive, Copy, Clone)]
+#[derive(FromPrimitive, Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Key {
     Any,          // 0x0
@@ -136,7 +307,7 @@ pub enum Key {
     Slash,        // 0x35
     RightShift,   // 0x36
     KeypadStar,   // 0x37
-    LeftCtrl,     // 0x38
+    LeftAlt,      // 0x38
     Spacebar,     // 0x39
     CapsLock,     // 0x3a
     F1,           // 0x3b
@@ -164,7 +335,312 @@ pub enum Key {
     Keypad3,      // 0x51
     Keypad0,      // 0x52
     KeypadDot,    // 0x53
-    F11 = 0x57,   // 0x54
-    F12,          // 0x55
-                  // TODO: Everything from here on are escaped sequences. i.e. 2 byte sequences initiated with 0xE0
+    // The extra key next to left shift on ISO keyboards
+    NonUsBackslash = 0x56, // 0x56
+    F11,                   // 0x57
+    F12,                   // 0x58
+
+    // Extended keys, sent as 0xE0 followed by a second byte. They're numbered
+    // by that byte with the high bit set
+    KeypadEnter = 0x9c,  // 0xe0 0x1c
+    RightControl = 0x9d, // 0xe0 0x1d
+    KeypadSlash = 0xb5,  // 0xe0 0x35
+    PrintScreen = 0xb7,  // 0xe0 0x2a 0xe0 0x37
+    RightAlt = 0xb8,     // 0xe0 0x38
+    Pause = 0xc5,        // 0xe1 0x1d 0x45 0xe1 0x9d 0xc5
+    Home = 0xc7,         // 0xe0 0x47
+    Up = 0xc8,           // 0xe0 0x48
+    PageUp = 0xc9,       // 0xe0 0x49
+    Left = 0xcb,         // 0xe0 0x4b
+    Right = 0xcd,        // 0xe0 0x4d
+    End = 0xcf,          // 0xe0 0x4f
+    Down = 0xd0,         // 0xe0 0x50
+    PageDown = 0xd1,     // 0xe0 0x51
+    Insert = 0xd2,       // 0xe0 0x52
+    Delete = 0xd3,       // 0xe0 0x53
+    LeftGui = 0xdb,      // 0xe0 0x5b
+    RightGui = 0xdc,     // 0xe0 0x5c
+    Menu = 0xdd,         // 0xe0 0x5d
+}
+
+// Tests
+
+/// Every key, along with the scancode set 1 sequence it's expected to produce
+#[cfg(test)]
+#[rustfmt::skip]
+const SCANCODES: &[(&[u8], Key)] = &[
+    (&[0x00], Key::Any),         (&[0x01], Key::Esc),          (&[0x02], Key::Num1),
+    (&[0x03], Key::Num2),        (&[0x04], Key::Num3),         (&[0x05], Key::Num4),
+    (&[0x06], Key::Num5),        (&[0x07], Key::Num6),         (&[0x08], Key::Num7),
+    (&[0x09], Key::Num8),        (&[0x0a], Key::Num9),         (&[0x0b], Key::Num0),
+    (&[0x0c], Key::Minus),       (&[0x0d], Key::Equals),       (&[0x0e], Key::Backspace),
+    (&[0x0f], Key::Tab),         (&[0x10], Key::Q),            (&[0x11], Key::W),
+    (&[0x12], Key::E),           (&[0x13], Key::R),            (&[0x14], Key::T),
+    (&[0x15], Key::Y),           (&[0x16], Key::U),            (&[0x17], Key::I),
+    (&[0x18], Key::O),           (&[0x19], Key::P),            (&[0x1a], Key::LeftBracket),
+    (&[0x1b], Key::RightBracket), (&[0x1c], Key::Enter),        (&[0x1d], Key::LeftControl),
+    (&[0x1e], Key::A),           (&[0x1f], Key::S),            (&[0x20], Key::D),
+    (&[0x21], Key::F),           (&[0x22], Key::G),            (&[0x23], Key::H),
+    (&[0x24], Key::J),           (&[0x25], Key::K),            (&[0x26], Key::L),
+    (&[0x27], Key::SemiColon),   (&[0x28], Key::Apostrophe),   (&[0x29], Key::Backtick),
+    (&[0x2a], Key::LeftShift),   (&[0x2b], Key::Backslash),    (&[0x2c], Key::Z),
+    (&[0x2d], Key::X),           (&[0x2e], Key::C),            (&[0x2f], Key::V),
+    (&[0x30], Key::B),           (&[0x31], Key::N),            (&[0x32], Key::M),
+    (&[0x33], Key::Comma),       (&[0x34], Key::Dot),          (&[0x35], Key::Slash),
+    (&[0x36], Key::RightShift),  (&[0x37], Key::KeypadStar),   (&[0x38], Key::LeftAlt),
+    (&[0x39], Key::Spacebar),    (&[0x3a], Key::CapsLock),     (&[0x3b], Key::F1),
+    (&[0x3c], Key::F2),          (&[0x3d], Key::F3),           (&[0x3e], Key::F4),
+    (&[0x3f], Key::F5),          (&[0x40], Key::F6),           (&[0x41], Key::F7),
+    (&[0x42], Key::F8),          (&[0x43], Key::F9),           (&[0x44], Key::F10),
+    (&[0x45], Key::NumLock),     (&[0x46], Key::ScrollLock),   (&[0x47], Key::Keypad7),
+    (&[0x48], Key::Keypad8),     (&[0x49], Key::Keypad9),      (&[0x4a], Key::KeypadMinus),
+    (&[0x4b], Key::Keypad4),     (&[0x4c], Key::Keypad5),      (&[0x4d], Key::Keypad6),
+    (&[0x4e], Key::KeypadPlus),  (&[0x4f], Key::Keypad1),      (&[0x50], Key::Keypad2),
+    (&[0x51], Key::Keypad3),     (&[0x52], Key::Keypad0),      (&[0x53], Key::KeypadDot),
+    (&[0x56], Key::NonUsBackslash), (&[0x57], Key::F11),       (&[0x58], Key::F12),
+    (&[0xe0, 0x1c], Key::KeypadEnter), (&[0xe0, 0x1d], Key::RightControl),
+    (&[0xe0, 0x35], Key::KeypadSlash), (&[0xe0, 0x38], Key::RightAlt),
+    (&[0xe0, 0x47], Key::Home),        (&[0xe0, 0x48], Key::Up),
+    (&[0xe0, 0x49], Key::PageUp),      (&[0xe0, 0x4b], Key::Left),
+    (&[0xe0, 0x4d], Key::Right),       (&[0xe0, 0x4f], Key::End),
+    (&[0xe0, 0x50], Key::Down),        (&[0xe0, 0x51], Key::PageDown),
+    (&[0xe0, 0x52], Key::Insert),      (&[0xe0, 0x53], Key::Delete),
+    (&[0xe0, 0x5b], Key::LeftGui),     (&[0xe0, 0x5c], Key::RightGui),
+    (&[0xe0, 0x5d], Key::Menu),
+];
+
+/// Keys whose break sequence isn't the make sequence with the high bit of the
+/// last byte set, along with their make and break sequences. Pause has no
+/// break sequence
+#[cfg(test)]
+const MULTI_BYTE_SCANCODES: &[(&[u8], Option<&[u8]>, Key)] = &[
+    (
+        &[0xe0, 0x2a, 0xe0, 0x37],
+        Some(&[0xe0, 0xb7, 0xe0, 0xaa]),
+        Key::PrintScreen,
+    ),
+    (&[0xe1, 0x1d, 0x45, 0xe1, 0x9d, 0xc5], None, Key::Pause),
+];
+
+/// Feed `bytes` to a fresh decoder, returning the event produced by the last
+#[cfg(test)]
+fn decode_sequence(bytes: &[u8]) -> Option<KeyboardEvent> {
+    let mut decoder = ScancodeDecoder::new();
+    let (last, prefix) = bytes.split_last().unwrap();
+    for byte in prefix {
+        assert_eq!(
+            decoder.decode(*byte),
+            None,
+            "Incomplete sequence {:x?}",
+            bytes
+        );
+    }
+    decoder.decode(*last)
+}
+
+#[test_case]
+fn every_make_code_is_decoded() {
+    for &(sequence, key) in SCANCODES {
+        assert_eq!(
+            decode_sequence(sequence),
+            Some(KeyboardEvent::Make(key)),
+            "{:x?}",
+            sequence
+        );
+    }
+}
+
+#[test_case]
+fn every_break_code_is_decoded() {
+    let mut sequence = [0u8; 2];
+    for &(make, key) in SCANCODES {
+        // Break codes are the make code, with the high bit of the last byte set
+        let sequence = &mut sequence[..make.len()];
+        sequence.copy_from_slice(make);
+        sequence[make.len() - 1] |= 0x80;
+        let event = decode_sequence(sequence);
+        assert_eq!(event, Some(KeyboardEvent::Break(key)), "{:x?}", sequence);
+        assert_eq!(event.unwrap().key(), key);
+        assert_eq!(event.unwrap().state(), KeyState::Up);
+    }
+}
+
+/// Feed `bytes` to a fresh decoder, returning the only event they produce
+#[cfg(test)]
+fn decode_single(bytes: &[u8]) -> Option<KeyboardEvent> {
+    let mut decoder = ScancodeDecoder::new();
+    let mut events = bytes.iter().filter_map(|byte| decoder.decode(*byte));
+    let event = events.next();
+    assert_eq!(events.next(), None, "More than one event from {:x?}", bytes);
+    event
+}
+
+#[test_case]
+fn every_multi_byte_sequence_is_decoded() {
+    for &(make, break_sequence, key) in MULTI_BYTE_SCANCODES {
+        assert_eq!(
+            decode_single(make),
+            Some(KeyboardEvent::Make(key)),
+            "{:x?}",
+            make
+        );
+        // Without a break sequence, `decode_single` has already checked
+        // that no release came out of the make sequence
+        if let Some(sequence) = break_sequence {
+            assert_eq!(
+                decode_single(sequence),
+                Some(KeyboardEvent::Break(key)),
+                "{:x?}",
+                sequence
+            );
+        }
+    }
+}
+
+#[test_case]
+fn unused_scancodes_are_ignored() {
+    for code in [0x54, 0x55, 0x59, 0x7f].iter() {
+        assert_eq!(decode_sequence(&[*code]), None);
+        assert_eq!(decode_sequence(&[*code | 0x80]), None);
+    }
+}
+
+#[test_case]
+fn break_releases_modifier() {
+    let mut keyboard = Keyboard::new(&layout::US);
+    keyboard.process_scancode(0x1d);
+    keyboard.process_scancode(0xe0);
+    keyboard.process_scancode(0x1d);
+    keyboard.process_scancode(0x9d);
+    assert!(keyboard.modifiers().ctrl());
+    keyboard.process_scancode(0xe0);
+    keyboard.process_scancode(0x9d);
+    assert!(!keyboard.modifiers().ctrl());
 }
+
+#[test_case]
+fn shift_produces_uppercase() {
+    let mut keyboard = Keyboard::new(&layout::US);
+    assert_eq!(keyboard.process_char(0x1e), Some('a'));
+    keyboard.process_scancode(0x2a); // left shift
+    assert_eq!(keyboard.process_char(0x1e), Some('A'));
+    assert_eq!(keyboard.process_char(0x03), Some('@'));
+    keyboard.process_scancode(0xaa); // left shift released
+    assert_eq!(keyboard.process_char(0x1e), Some('a'));
+}
+
+#[test_case]
+fn caps_lock_toggles_letters_only() {
+    let mut keyboard = Keyboard::new(&layout::US);
+    keyboard.process_scancode(0x3a);
+    keyboard.process_scancode(0x3a); // typematic repeat must not toggle back
+    keyboard.process_scancode(0xba);
+    assert!(keyboard.modifiers().caps_lock);
+    assert_eq!(keyboard.process_char(0x1e), Some('A'));
+    assert_eq!(keyboard.process_char(0x02), Some('1'));
+    keyboard.process_scancode(0x36); // right shift
+    assert_eq!(keyboard.process_char(0x1e), Some('a'));
+}
+
+#[test_case]
+fn releases_produce_no_characters() {
+    let mut keyboard = Keyboard::new(&layout::US);
+    let event = keyboard.process_scancode(0x9e).unwrap();
+    assert_eq!(event.key, Key::A);
+    assert_eq!(event.state, KeyState::Up);
+    assert_eq!(keyboard.translate(&event), None);
+}
+
+#[test_case]
+fn ctrl_produces_control_characters() {
+    let mut keyboard = Keyboard::new(&layout::US);
+    keyboard.process_scancode(0x1d);
+    assert_eq!(keyboard.process_char(0x2e), Some('\x03'));
+}
+
+#[test_case]
+fn layouts_are_swappable() {
+    let mut keyboard = Keyboard::new(&layout::DANISH);
+    assert_eq!(keyboard.process_char(0x27), Some('æ'));
+    assert_eq!(keyboard.process_char(0x1a), Some('å'));
+    keyboard.set_layout(&layout::DVORAK);
+    assert_eq!(keyboard.process_char(0x10), Some('\''));
+    assert_eq!(keyboard.process_char(0x1f), Some('o'));
+    keyboard.set_layout(&layout::UK);
+    keyboard.process_scancode(0x2a);
+    assert_eq!(keyboard.process_char(0x03), Some('"'));
+}
+
+#[test_case]
+fn alt_gr_selects_third_level() {
+    let mut keyboard = Keyboard::new(&layout::UK);
+    keyboard.process_scancode(0x1d);
+    keyboard.process_scancode(0x38);
+    assert!(keyboard.modifiers().alt_gr());
+    assert_eq!(keyboard.process_char(0x05), Some('€'));
+}
+
+#[test_case]
+fn right_alt_is_alt_gr() {
+    let mut keyboard = Keyboard::new(&layout::DANISH);
+    keyboard.process_scancode(0xe0);
+    keyboard.process_scancode(0x38);
+    assert_eq!(keyboard.process_char(0x03), Some('@'));
+    keyboard.process_scancode(0xe0);
+    keyboard.process_scancode(0xb8);
+    assert_eq!(keyboard.process_char(0x03), Some('2'));
+}
+
+#[cfg(test)]
+fn decode_all(bytes: &[u8]) -> [Option<KeyboardEvent>; 8] {
+    let mut decoder = ScancodeDecoder::new();
+    let mut events = [None; 8];
+    for (event, byte) in events.iter_mut().zip(bytes) {
+        *event = decoder.decode(*byte);
+    }
+    events
+}
+
+#[test_case]
+fn extended_keys_are_decoded() {
+    let events = decode_all(&[0xe0, 0x48, 0xe0, 0xc8, 0xe0, 0x1c, 0x1c]);
+    assert_eq!(events[0], None);
+    assert_eq!(events[1], Some(KeyboardEvent::Make(Key::Up)));
+    assert_eq!(events[2], None);
+    assert_eq!(events[3], Some(KeyboardEvent::Break(Key::Up)));
+    assert_eq!(events[5], Some(KeyboardEvent::Make(Key::KeypadEnter)));
+    assert_eq!(events[6], Some(KeyboardEvent::Make(Key::Enter)));
+}
+
+#[test_case]
+fn print_screen_is_decoded() {
+    let pressed = decode_all(&[0xe0, 0x2a, 0xe0, 0x37]);
+    assert_eq!(pressed[..3], [None; 3]);
+    assert_eq!(pressed[3], Some(KeyboardEvent::Make(Key::PrintScreen)));
+
+    let released = decode_all(&[0xe0, 0xb7, 0xe0, 0xaa]);
+    assert_eq!(released[1], Some(KeyboardEvent::Break(Key::PrintScreen)));
+    assert_eq!(released[2..4], [None; 2]);
+}
+
+#[test_case]
+fn pause_is_decoded() {
+    let events = decode_all(&[0xe1, 0x1d, 0x45, 0xe1, 0x9d, 0xc5, 0x1e]);
+    assert_eq!(events[..5], [None; 5]);
+    assert_eq!(events[5], Some(KeyboardEvent::Make(Key::Pause)));
+    assert_eq!(events[6], Some(KeyboardEvent::Make(Key::A)));
+    // 0x9d and 0xc5 look like break codes, but Pause is never released
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, Some(KeyboardEvent::Break(_)))));
+}
+
+#[test_case]
+fn num_lock_enables_keypad_digits() {
+    let mut keyboard = Keyboard::new(&layout::US);
+    assert_eq!(keyboard.process_char(0x4f), None);
+    keyboard.process_scancode(0x45);
+    assert_eq!(keyboard.process_char(0x4f), Some('1'));
+}
+
+// end of tests
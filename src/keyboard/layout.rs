@@ -0,0 +1,304 @@
+use super::{Key, Modifiers};
+
+/// Characters a key produces on its own, with shift, and with AltGr. `'\0'`
+/// means the key produces nothing with that modifier
+type Mapping = (Key, char, char, char);
+
+/// Table mapping physical keys to characters
+pub struct Layout {
+    pub name: &'static str,
+    keys: &'static [Mapping],
+}
+
+impl Layout {
+    /// Translate `key` to a character, given the state of the modifiers
+    pub fn map(&self, key: Key, modifiers: &Modifiers) -> Option<char> {
+        if let Some(c) = map_common(key, modifiers) {
+            return Some(c);
+        }
+
+        let &(_, normal, shifted, alt_gr) = self.keys.iter().find(|m| m.0 == key)?;
+        let c = if modifiers.alt_gr() {
+            alt_gr
+        } else {
+            // Caps lock only affects letters, and is inverted by shift
+            let shift = modifiers.shift() ^ (modifiers.caps_lock && normal.is_alphabetic());
+            if shift {
+                shifted
+            } else {
+                normal
+            }
+        };
+
+        match c {
+            '\0' => None,
+            // Ctrl+letter produces the corresponding control character
+            c if modifiers.ctrl() && !modifiers.alt_gr() && c.is_ascii_alphabetic() => {
+                Some((c as u8 & 0x1f) as char)
+            }
+            c => Some(c),
+        }
+    }
+}
+
+/// Keys that produce the same character regardless of layout
+fn map_common(key: Key, modifiers: &Modifiers) -> Option<char> {
+    let c = match key {
+        Key::Esc => '\x1b',
+        Key::Backspace => '\x08',
+        Key::Tab => '\t',
+        Key::Enter | Key::KeypadEnter => '\n',
+        Key::Spacebar => ' ',
+        Key::KeypadSlash => '/',
+        Key::KeypadStar => '*',
+        Key::KeypadMinus => '-',
+        Key::KeypadPlus => '+',
+        _ if !modifiers.num_lock => return None,
+        Key::Keypad0 => '0',
+        Key::Keypad1 => '1',
+        Key::Keypad2 => '2',
+        Key::Keypad3 => '3',
+        Key::Keypad4 => '4',
+        Key::Keypad5 => '5',
+        Key::Keypad6 => '6',
+        Key::Keypad7 => '7',
+        Key::Keypad8 => '8',
+        Key::Keypad9 => '9',
+        Key::KeypadDot => '.',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// US QWERTY
+#[rustfmt::skip]
+pub static US: Layout = Layout {
+    name: "us",
+    keys: &[
+        (Key::Backtick,       '`',  '~',  '\0'),
+        (Key::Num1,           '1',  '!',  '\0'),
+        (Key::Num2,           '2',  '@',  '\0'),
+        (Key::Num3,           '3',  '#',  '\0'),
+        (Key::Num4,           '4',  '$',  '\0'),
+        (Key::Num5,           '5',  '%',  '\0'),
+        (Key::Num6,           '6',  '^',  '\0'),
+        (Key::Num7,           '7',  '&',  '\0'),
+        (Key::Num8,           '8',  '*',  '\0'),
+        (Key::Num9,           '9',  '(',  '\0'),
+        (Key::Num0,           '0',  ')',  '\0'),
+        (Key::Minus,          '-',  '_',  '\0'),
+        (Key::Equals,         '=',  '+',  '\0'),
+        (Key::Q,              'q',  'Q',  '\0'),
+        (Key::W,              'w',  'W',  '\0'),
+        (Key::E,              'e',  'E',  '\0'),
+        (Key::R,              'r',  'R',  '\0'),
+        (Key::T,              't',  'T',  '\0'),
+        (Key::Y,              'y',  'Y',  '\0'),
+        (Key::U,              'u',  'U',  '\0'),
+        (Key::I,              'i',  'I',  '\0'),
+        (Key::O,              'o',  'O',  '\0'),
+        (Key::P,              'p',  'P',  '\0'),
+        (Key::LeftBracket,    '[',  '{',  '\0'),
+        (Key::RightBracket,   ']',  '}',  '\0'),
+        (Key::Backslash,      '\\', '|',  '\0'),
+        (Key::A,              'a',  'A',  '\0'),
+        (Key::S,              's',  'S',  '\0'),
+        (Key::D,              'd',  'D',  '\0'),
+        (Key::F,              'f',  'F',  '\0'),
+        (Key::G,              'g',  'G',  '\0'),
+        (Key::H,              'h',  'H',  '\0'),
+        (Key::J,              'j',  'J',  '\0'),
+        (Key::K,              'k',  'K',  '\0'),
+        (Key::L,              'l',  'L',  '\0'),
+        (Key::SemiColon,      ';',  ':',  '\0'),
+        (Key::Apostrophe,     '\'', '"',  '\0'),
+        (Key::NonUsBackslash, '\\', '|',  '\0'),
+        (Key::Z,              'z',  'Z',  '\0'),
+        (Key::X,              'x',  'X',  '\0'),
+        (Key::C,              'c',  'C',  '\0'),
+        (Key::V,              'v',  'V',  '\0'),
+        (Key::B,              'b',  'B',  '\0'),
+        (Key::N,              'n',  'N',  '\0'),
+        (Key::M,              'm',  'M',  '\0'),
+        (Key::Comma,          ',',  '<',  '\0'),
+        (Key::Dot,            '.',  '>',  '\0'),
+        (Key::Slash,          '/',  '?',  '\0'),
+    ],
+};
+
+/// UK QWERTY
+#[rustfmt::skip]
+pub static UK: Layout = Layout {
+    name: "uk",
+    keys: &[
+        (Key::Backtick,       '`',  '¬',  '¦'),
+        (Key::Num1,           '1',  '!',  '\0'),
+        (Key::Num2,           '2',  '"',  '\0'),
+        (Key::Num3,           '3',  '£',  '\0'),
+        (Key::Num4,           '4',  '$',  '€'),
+        (Key::Num5,           '5',  '%',  '\0'),
+        (Key::Num6,           '6',  '^',  '\0'),
+        (Key::Num7,           '7',  '&',  '\0'),
+        (Key::Num8,           '8',  '*',  '\0'),
+        (Key::Num9,           '9',  '(',  '\0'),
+        (Key::Num0,           '0',  ')',  '\0'),
+        (Key::Minus,          '-',  '_',  '\0'),
+        (Key::Equals,         '=',  '+',  '\0'),
+        (Key::Q,              'q',  'Q',  '\0'),
+        (Key::W,              'w',  'W',  '\0'),
+        (Key::E,              'e',  'E',  'é'),
+        (Key::R,              'r',  'R',  '\0'),
+        (Key::T,              't',  'T',  '\0'),
+        (Key::Y,              'y',  'Y',  '\0'),
+        (Key::U,              'u',  'U',  'ú'),
+        (Key::I,              'i',  'I',  'í'),
+        (Key::O,              'o',  'O',  'ó'),
+        (Key::P,              'p',  'P',  '\0'),
+        (Key::LeftBracket,    '[',  '{',  '\0'),
+        (Key::RightBracket,   ']',  '}',  '\0'),
+        (Key::Backslash,      '#',  '~',  '\0'),
+        (Key::A,              'a',  'A',  'á'),
+        (Key::S,              's',  'S',  '\0'),
+        (Key::D,              'd',  'D',  '\0'),
+        (Key::F,              'f',  'F',  '\0'),
+        (Key::G,              'g',  'G',  '\0'),
+        (Key::H,              'h',  'H',  '\0'),
+        (Key::J,              'j',  'J',  '\0'),
+        (Key::K,              'k',  'K',  '\0'),
+        (Key::L,              'l',  'L',  '\0'),
+        (Key::SemiColon,      ';',  ':',  '\0'),
+        (Key::Apostrophe,     '\'', '@',  '\0'),
+        (Key::NonUsBackslash, '\\', '|',  '\0'),
+        (Key::Z,              'z',  'Z',  '\0'),
+        (Key::X,              'x',  'X',  '\0'),
+        (Key::C,              'c',  'C',  '\0'),
+        (Key::V,              'v',  'V',  '\0'),
+        (Key::B,              'b',  'B',  '\0'),
+        (Key::N,              'n',  'N',  '\0'),
+        (Key::M,              'm',  'M',  '\0'),
+        (Key::Comma,          ',',  '<',  '\0'),
+        (Key::Dot,            '.',  '>',  '\0'),
+        (Key::Slash,          '/',  '?',  '\0'),
+    ],
+};
+
+/// Danish QWERTY. The accent keys are treated as regular keys, as dead keys
+/// aren't supported
+#[rustfmt::skip]
+pub static DANISH: Layout = Layout {
+    name: "dk",
+    keys: &[
+        (Key::Backtick,       '½',  '§',  '\0'),
+        (Key::Num1,           '1',  '!',  '\0'),
+        (Key::Num2,           '2',  '"',  '@'),
+        (Key::Num3,           '3',  '#',  '£'),
+        (Key::Num4,           '4',  '¤',  '$'),
+        (Key::Num5,           '5',  '%',  '€'),
+        (Key::Num6,           '6',  '&',  '\0'),
+        (Key::Num7,           '7',  '/',  '{'),
+        (Key::Num8,           '8',  '(',  '['),
+        (Key::Num9,           '9',  ')',  ']'),
+        (Key::Num0,           '0',  '=',  '}'),
+        (Key::Minus,          '+',  '?',  '\0'),
+        (Key::Equals,         '´',  '`',  '|'),
+        (Key::Q,              'q',  'Q',  '\0'),
+        (Key::W,              'w',  'W',  '\0'),
+        (Key::E,              'e',  'E',  '€'),
+        (Key::R,              'r',  'R',  '\0'),
+        (Key::T,              't',  'T',  '\0'),
+        (Key::Y,              'y',  'Y',  '\0'),
+        (Key::U,              'u',  'U',  '\0'),
+        (Key::I,              'i',  'I',  '\0'),
+        (Key::O,              'o',  'O',  '\0'),
+        (Key::P,              'p',  'P',  '\0'),
+        (Key::LeftBracket,    'å',  'Å',  '\0'),
+        (Key::RightBracket,   '¨',  '^',  '~'),
+        (Key::Backslash,      '\'', '*',  '\0'),
+        (Key::A,              'a',  'A',  '\0'),
+        (Key::S,              's',  'S',  '\0'),
+        (Key::D,              'd',  'D',  '\0'),
+        (Key::F,              'f',  'F',  '\0'),
+        (Key::G,              'g',  'G',  '\0'),
+        (Key::H,              'h',  'H',  '\0'),
+        (Key::J,              'j',  'J',  '\0'),
+        (Key::K,              'k',  'K',  '\0'),
+        (Key::L,              'l',  'L',  '\0'),
+        (Key::SemiColon,      'æ',  'Æ',  '\0'),
+        (Key::Apostrophe,     'ø',  'Ø',  '\0'),
+        (Key::NonUsBackslash, '<',  '>',  '\\'),
+        (Key::Z,              'z',  'Z',  '\0'),
+        (Key::X,              'x',  'X',  '\0'),
+        (Key::C,              'c',  'C',  '\0'),
+        (Key::V,              'v',  'V',  '\0'),
+        (Key::B,              'b',  'B',  '\0'),
+        (Key::N,              'n',  'N',  '\0'),
+        (Key::M,              'm',  'M',  'µ'),
+        (Key::Comma,          ',',  ';',  '\0'),
+        (Key::Dot,            '.',  ':',  '\0'),
+        (Key::Slash,          '-',  '_',  '\0'),
+    ],
+};
+
+/// US Dvorak
+#[rustfmt::skip]
+pub static DVORAK: Layout = Layout {
+    name: "dvorak",
+    keys: &[
+        (Key::Backtick,       '`',  '~',  '\0'),
+        (Key::Num1,           '1',  '!',  '\0'),
+        (Key::Num2,           '2',  '@',  '\0'),
+        (Key::Num3,           '3',  '#',  '\0'),
+        (Key::Num4,           '4',  '$',  '\0'),
+        (Key::Num5,           '5',  '%',  '\0'),
+        (Key::Num6,           '6',  '^',  '\0'),
+        (Key::Num7,           '7',  '&',  '\0'),
+        (Key::Num8,           '8',  '*',  '\0'),
+        (Key::Num9,           '9',  '(',  '\0'),
+        (Key::Num0,           '0',  ')',  '\0'),
+        (Key::Minus,          '[',  '{',  '\0'),
+        (Key::Equals,         ']',  '}',  '\0'),
+        (Key::Q,              '\'', '"',  '\0'),
+        (Key::W,              ',',  '<',  '\0'),
+        (Key::E,              '.',  '>',  '\0'),
+        (Key::R,              'p',  'P',  '\0'),
+        (Key::T,              'y',  'Y',  '\0'),
+        (Key::Y,              'f',  'F',  '\0'),
+        (Key::U,              'g',  'G',  '\0'),
+        (Key::I,              'c',  'C',  '\0'),
+        (Key::O,              'r',  'R',  '\0'),
+        (Key::P,              'l',  'L',  '\0'),
+        (Key::LeftBracket,    '/',  '?',  '\0'),
+        (Key::RightBracket,   '=',  '+',  '\0'),
+        (Key::Backslash,      '\\', '|',  '\0'),
+        (Key::A,              'a',  'A',  '\0'),
+        (Key::S,              'o',  'O',  '\0'),
+        (Key::D,              'e',  'E',  '\0'),
+        (Key::F,              'u',  'U',  '\0'),
+        (Key::G,              'i',  'I',  '\0'),
+        (Key::H,              'd',  'D',  '\0'),
+        (Key::J,              'h',  'H',  '\0'),
+        (Key::K,              't',  'T',  '\0'),
+        (Key::L,              'n',  'N',  '\0'),
+        (Key::SemiColon,      's',  'S',  '\0'),
+        (Key::Apostrophe,     '-',  '_',  '\0'),
+        (Key::NonUsBackslash, '\\', '|',  '\0'),
+        (Key::Z,              ';',  ':',  '\0'),
+        (Key::X,              'q',  'Q',  '\0'),
+        (Key::C,              'j',  'J',  '\0'),
+        (Key::V,              'k',  'K',  '\0'),
+        (Key::B,              'x',  'X',  '\0'),
+        (Key::N,              'b',  'B',  '\0'),
+        (Key::M,              'm',  'M',  '\0'),
+        (Key::Comma,          'w',  'W',  '\0'),
+        (Key::Dot,            'v',  'V',  '\0'),
+        (Key::Slash,          'z',  'Z',  '\0'),
+    ],
+};
+
+/// All built-in layouts
+pub static LAYOUTS: [&Layout; 4] = [&US, &UK, &DANISH, &DVORAK];
+
+/// Look up a built-in layout by name
+pub fn by_name(name: &str) -> Option<&'static Layout> {
+    LAYOUTS.iter().copied().find(|layout| layout.name == name)
+}
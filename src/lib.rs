@@ -13,13 +13,29 @@ extern crate num_derive;
 
 use core::{any::type_name, panic::PanicInfo};
 
+pub mod acpi;
 pub mod allocator;
+pub mod apic;
+pub mod boot_params;
+pub mod console;
+pub mod framebuffer;
 pub mod gdt;
+pub mod graphics;
+pub mod hpet;
 pub mod interrupts;
 pub mod keyboard;
+pub mod logger;
 pub mod memory;
+pub mod mouse;
+pub mod pci;
+pub mod pit;
+pub mod power;
+pub mod ps2;
+pub mod rtc;
 pub mod serial;
+pub mod shell;
 pub mod task;
+pub mod time;
 
 pub mod vga_buffer;
 
@@ -45,12 +61,32 @@ pub fn exit_qemu(exit_code: QExitCode) {
 
 /// Central place for initialisation
 pub fn init() {
+    logger::init();
+    boot_params::init(boot_params::BUILT_IN);
+    if let Some(filter) = boot_params::get().log_filter {
+        logger::set_filter(filter);
+    }
     gdt::init();
     interrupts::init_descriptor_table();
-    unsafe { interrupts::PICS.lock().initialize() };
+    interrupts::init_pics();
+    pit::set_frequency(pit::TIMER_FREQUENCY);
+    time::init();
+    if let Err(err) = ps2::init() {
+        log::warn!("Failed to initialise PS/2 controller: {:?}", err);
+    }
+    task::keyboard::init();
+    if let Err(err) = task::mouse::init() {
+        log::warn!("Failed to initialise PS/2 mouse: {:?}", err);
+    }
+    if let Err(err) = task::serial::init(serial::ComPort::Com1) {
+        log::warn!("Failed to set up serial input: {:?}", err);
+    }
     x86_64::instructions::interrupts::enable();
 }
 
+/// Halt forever. Used on fatal paths, so it doesn't touch the console: the
+/// code that was interrupted may still hold its locks. Deferred log messages
+/// are written out by the executors instead
 pub fn hlt_loop() -> ! {
     loop {
         x86_64::instructions::hlt();
@@ -77,7 +113,7 @@ pub fn kernel_test_entry(_boot_info: &'static BootInfo) -> ! {
 }
 
 /// Trait for test functions that prints their name, invokes them, and prints a
-/// success status message if it didn't panic
+/// success status message along with how long it took if it didn't panic
 pub trait Testable {
     fn run(&self) -> ();
 }
@@ -89,13 +125,16 @@ where
     fn run(&self) {
         let name: &str = type_name::<T>().split("::").last().unwrap();
         serial_print!("[01;34m{:.<80}[0m", name);
-        self();
-        serial_println!("[01;32m[ ✓ ][0m");
+        let elapsed = time::measure(|| self());
+        serial_println!("[01;32m[ ✓ ][0m {:?}", elapsed);
     }
 }
 
 /// Panic handler for the custom testing framework
 pub fn test_panic(_info: &PanicInfo) -> ! {
+    // The test may have panicked halfway through printing
+    x86_64::instructions::interrupts::disable();
+    unsafe { console::force_unlock() };
     serial_println!("[01;31m[ ✘ ][0m");
     serial_println!("   [01;31m┌{:─<78}┐[0m", "");
     serial_println!("   [01;31m│{:^78}│[0m", "x Test failed x");
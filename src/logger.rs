@@ -0,0 +1,345 @@
+use alloc::{string::String, vec};
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use self::deferred::{Message, Queue};
+use crate::{apic, console::ring::RingBuffer, interrupts, println, time, vga_buffer};
+
+pub mod deferred;
+
+/// Module filters a filter spec can hold
+pub const MAX_DIRECTIVES: usize = 16;
+/// Bytes of log messages kept for `dmesg`
+pub const LOG_SIZE: usize = 32 * 1024;
+
+/// Filter used until `set_filter` is called
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterError {
+    UnknownLevel,
+    TooManyDirectives,
+}
+
+/// Log level per module, parsed from specs like `warn,task=debug,ps2=off`.
+/// A bare level sets the default, `module=level` sets it for a module and
+/// everything inside it
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    default: LevelFilter,
+    directives: [(&'static str, LevelFilter); MAX_DIRECTIVES],
+    count: usize,
+}
+
+impl Filter {
+    pub const fn new(default: LevelFilter) -> Filter {
+        Filter {
+            default,
+            directives: [("", LevelFilter::Off); MAX_DIRECTIVES],
+            count: 0,
+        }
+    }
+
+    pub fn parse(spec: &'static str) -> Result<Filter, FilterError> {
+        let mut filter = Filter::new(DEFAULT_LEVEL);
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => filter.add(module.trim(), parse_level(level)?)?,
+                None => filter.default = parse_level(directive)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Log `module` and everything inside it at `level`
+    pub fn add(&mut self, module: &'static str, level: LevelFilter) -> Result<(), FilterError> {
+        let directives = &mut self.directives[..self.count];
+        if let Some(directive) = directives.iter_mut().find(|(m, _)| *m == module) {
+            directive.1 = level;
+            return Ok(());
+        }
+        let slot = self
+            .directives
+            .get_mut(self.count)
+            .ok_or(FilterError::TooManyDirectives)?;
+        *slot = (module, level);
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Level `target` is logged at. The most specific matching module wins
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives[..self.count]
+            .iter()
+            .filter(|(module, _)| module_matches(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Most verbose level anything is logged at
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives[..self.count]
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, FilterError> {
+    level.trim().parse().map_err(|_| FilterError::UnknownLevel)
+}
+
+/// Whether `target` is `module` or inside it. The crate name may be left out
+/// of `module`
+fn module_matches(target: &str, module: &str) -> bool {
+    let is_inside = |path: &str| match path.strip_prefix(module) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    };
+    is_inside(target)
+        || target
+            .split_once("::")
+            .map_or(false, |(_, path)| is_inside(path))
+}
+
+/// Log messages without colours, for `dmesg`
+static LOG: Mutex<RingBuffer<LOG_SIZE>> = Mutex::new(RingBuffer::new());
+// Checked from interrupt handlers, so it must only be locked with interrupts
+// disabled
+static FILTER: Mutex<Filter> = Mutex::new(Filter::new(DEFAULT_LEVEL));
+
+/// Messages logged from interrupt handlers, which can't take the console
+/// locks, so they're written out later by `drain`
+const NO_MESSAGES: Queue = Queue::new();
+static DEFERRED: [Queue; apic::MAX_CPUS] = [NO_MESSAGES; apic::MAX_CPUS];
+/// Deferred messages lost because their queue was full
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+/// Held while the deferred queues are drained, as they can only have one
+/// reader
+static DRAINING: Mutex<()> = Mutex::new(());
+
+struct KernelLogger;
+
+static LOGGER: KernelLogger = KernelLogger;
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= without_interrupts(|| FILTER.lock().level_for(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let uptime = time::uptime();
+        let (level, target) = (record.level(), record.target());
+        if interrupts::in_interrupt() {
+            let message =
+                Message::new(uptime, level, format_args!("{}: {}", target, record.args()));
+            if !DEFERRED[apic::cpu_index()].push(message) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
+        // Anything logged from interrupts so far happened first
+        drain();
+        write_message(uptime, level, format_args!("{}: {}", target, record.args()));
+    }
+
+    fn flush(&self) {
+        drain();
+    }
+}
+
+/// A log message as it's shown on the console
+struct Line<'a> {
+    uptime: Duration,
+    level: Level,
+    text: fmt::Arguments<'a>,
+}
+
+impl fmt::Display for Line<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:>5}.{:03}] \x1b[{}m{:<5}\x1b[0m {}",
+            self.uptime.as_secs(),
+            self.uptime.subsec_millis(),
+            level_color(self.level),
+            self.level,
+            self.text
+        )
+    }
+}
+
+fn write_message(uptime: Duration, level: Level, text: fmt::Arguments) {
+    let (seconds, millis) = (uptime.as_secs(), uptime.subsec_millis());
+    let line = Line {
+        uptime,
+        level,
+        text,
+    };
+    println!("{}", line);
+    vga_buffer::print_to(vga_buffer::LOG_TERMINAL, format_args!("{}\n", line));
+    without_interrupts(|| {
+        let mut log = LOG.lock();
+        let _ = writeln!(log, "[{:>5}.{:03}] {:<5} {}", seconds, millis, level, text);
+    });
+}
+
+/// Write out the messages interrupt handlers have logged since the last
+/// drain. Does nothing when called from an interrupt handler, or while
+/// another drain is in progress
+pub fn drain() {
+    if interrupts::in_interrupt() {
+        return;
+    }
+    let _draining = match DRAINING.try_lock() {
+        Some(guard) => guard,
+        None => return,
+    };
+    for queue in DEFERRED.iter() {
+        // Only ever popped with DRAINING held
+        while let Some(message) = unsafe { queue.pop() } {
+            write_message(
+                message.uptime,
+                message.level,
+                format_args!("{}", message.text()),
+            );
+        }
+    }
+    let dropped = DROPPED.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        write_message(
+            time::uptime(),
+            Level::Warn,
+            format_args!(
+                "{}: {} messages from interrupt handlers were dropped",
+                module_path!(),
+                dropped
+            ),
+        );
+    }
+}
+
+/// SGR colour parameter for messages at `level`
+fn level_color(level: Level) -> u8 {
+    match level {
+        Level::Error => 91,
+        Level::Warn => 93,
+        Level::Info => 92,
+        Level::Debug => 36,
+        Level::Trace => 90,
+    }
+}
+
+/// Install the kernel logger, logging at `Info` and above until a filter is
+/// set. Does nothing if it's already installed
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(DEFAULT_LEVEL);
+    }
+}
+
+pub fn set_filter(filter: Filter) {
+    without_interrupts(|| *FILTER.lock() = filter);
+    log::set_max_level(filter.max_level());
+}
+
+pub fn filter() -> Filter {
+    without_interrupts(|| *FILTER.lock())
+}
+
+/// Copy the newest log messages into `out`, returning how many bytes were
+/// copied. Doesn't allocate, so it's usable after a crash
+pub fn read_newest(out: &mut [u8]) -> usize {
+    without_interrupts(|| LOG.lock().read_newest(out))
+}
+
+/// Every log message kept, oldest first
+pub fn dmesg() -> String {
+    let mut bytes = vec![0; LOG_SIZE];
+    let len = without_interrupts(|| LOG.lock().read(&mut bytes));
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+// Tests
+
+#[test_case]
+fn filters_are_parsed() {
+    let filter = Filter::parse("warn, task=debug,blight_os::task::keyboard=trace").unwrap();
+    assert_eq!(filter.level_for("blight_os::ps2"), LevelFilter::Warn);
+    assert_eq!(filter.level_for("blight_os::task"), LevelFilter::Debug);
+    assert_eq!(
+        filter.level_for("blight_os::task::mouse"),
+        LevelFilter::Debug
+    );
+    assert_eq!(
+        filter.level_for("blight_os::task::keyboard"),
+        LevelFilter::Trace
+    );
+    assert_eq!(filter.level_for("blight_os::tasks"), LevelFilter::Warn);
+    assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+    let filter = Filter::parse("").unwrap();
+    assert_eq!(filter.level_for("anything"), DEFAULT_LEVEL);
+}
+
+#[test_case]
+fn bad_filters_are_rejected() {
+    assert_eq!(
+        Filter::parse("task=loud").unwrap_err(),
+        FilterError::UnknownLevel
+    );
+    assert_eq!(
+        Filter::parse(
+            "a=info,b=info,c=info,d=info,e=info,f=info,g=info,h=info,i=info,\
+                       j=info,k=info,l=info,m=info,n=info,o=info,p=info,q=info"
+        )
+        .unwrap_err(),
+        FilterError::TooManyDirectives
+    );
+}
+
+#[test_case]
+fn messages_are_kept_for_dmesg() {
+    log::info!("Kept for dmesg");
+    log::debug!("Filtered out");
+    let mut tail = [0; 15];
+    assert_eq!(read_newest(&mut tail), tail.len());
+    assert_eq!(&tail, b"Kept for dmesg\n");
+}
+
+#[test_case]
+fn modules_can_be_silenced() {
+    let previous = filter();
+    set_filter(Filter::parse("info,logger=off").unwrap());
+    log::info!("Silenced");
+    set_filter(previous);
+    let mut tail = [0; 9];
+    read_newest(&mut tail);
+    assert_ne!(&tail, b"Silenced\n");
+}
+
+#[test_case]
+fn interrupt_handlers_defer_messages() {
+    log::info!("Before the breakpoint");
+    x86_64::instructions::interrupts::int3();
+    let mut tail = [0; 22];
+    read_newest(&mut tail);
+    assert_eq!(&tail, b"Before the breakpoint\n");
+
+    let mut tail = [0; 128];
+    drain();
+    let len = read_newest(&mut tail);
+    assert!(tail[..len].windows(13).any(|w| w == b"Breakpoint at"));
+}
+
+// end of tests
@@ -0,0 +1,208 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use log::Level;
+
+/// Messages each CPU can hold before interrupt handlers start losing them
+pub const QUEUE_SIZE: usize = 32;
+/// Longest message kept, including the target. Longer ones are cut short
+pub const MESSAGE_SIZE: usize = 120;
+
+/// A log message from an interrupt handler, waiting to be written out
+#[derive(Clone, Copy)]
+pub struct Message {
+    pub uptime: Duration,
+    pub level: Level,
+    len: usize,
+    text: [u8; MESSAGE_SIZE],
+}
+
+impl Message {
+    const fn empty() -> Message {
+        Message {
+            uptime: Duration::from_secs(0),
+            level: Level::Error,
+            len: 0,
+            text: [0; MESSAGE_SIZE],
+        }
+    }
+
+    /// Format `args` into a message, cutting it short at a character
+    /// boundary if it doesn't fit
+    pub fn new(uptime: Duration, level: Level, args: fmt::Arguments) -> Message {
+        let mut message = Message {
+            uptime,
+            level,
+            ..Message::empty()
+        };
+        let _ = message.write_fmt(args);
+        message
+    }
+
+    pub fn text(&self) -> &str {
+        // Only whole characters are ever copied in
+        core::str::from_utf8(&self.text[..self.len]).unwrap_or("")
+    }
+}
+
+impl fmt::Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let space = MESSAGE_SIZE - self.len;
+        let mut end = s.len().min(space);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.text[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+        if end < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+struct Slot {
+    /// Twice the lap the slot is on, plus one while it holds a message
+    stamp: AtomicUsize,
+    message: UnsafeCell<Message>,
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        stamp: AtomicUsize::new(0),
+        message: UnsafeCell::new(Message::empty()),
+    };
+}
+
+/// Bounded queue of messages that never blocks. Any number of interrupt
+/// handlers can push at once, but only one reader may pop at a time
+pub struct Queue {
+    slots: [Slot; QUEUE_SIZE],
+    /// Position the next message is pushed at
+    head: AtomicUsize,
+    /// Position the next message is popped from
+    tail: AtomicUsize,
+}
+
+// Slots are only accessed by whoever claimed them through their stamp
+unsafe impl Sync for Queue {}
+
+impl Queue {
+    pub const fn new() -> Queue {
+        Queue {
+            slots: [Slot::EMPTY; QUEUE_SIZE],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add `message` to the queue. Returns false, dropping it, if the queue
+    /// is full
+    pub fn push(&self, message: Message) -> bool {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[head % QUEUE_SIZE];
+            let empty = head / QUEUE_SIZE * 2;
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            if stamp == empty {
+                match self.head.compare_exchange_weak(
+                    head,
+                    head.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { *slot.message.get() = message };
+                        slot.stamp.store(empty + 1, Ordering::Release);
+                        return true;
+                    }
+                    Err(current) => head = current,
+                }
+            } else if stamp < empty {
+                // Still holds a message from the previous lap
+                return false;
+            } else {
+                // Someone else pushed here first
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Take the oldest message out of the queue.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called from more than one place at a time
+    pub unsafe fn pop(&self) -> Option<Message> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let slot = &self.slots[tail % QUEUE_SIZE];
+        let full = tail / QUEUE_SIZE * 2 + 1;
+        if slot.stamp.load(Ordering::Acquire) != full {
+            return None;
+        }
+        let message = *slot.message.get();
+        slot.stamp.store(full + 1, Ordering::Release);
+        self.tail.store(tail.wrapping_add(1), Ordering::Relaxed);
+        Some(message)
+    }
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tests
+
+#[test_case]
+fn messages_come_out_in_order() {
+    let queue = Queue::new();
+    for round in 0..3 {
+        for i in 0..QUEUE_SIZE {
+            let message = Message::new(
+                Duration::from_millis(i as u64),
+                Level::Info,
+                format_args!("{}", round),
+            );
+            assert!(queue.push(message));
+        }
+        for i in 0..QUEUE_SIZE {
+            let message = unsafe { queue.pop() }.unwrap();
+            assert_eq!(message.uptime, Duration::from_millis(i as u64));
+            assert_eq!(message.text().parse(), Ok(round));
+        }
+        assert!(unsafe { queue.pop() }.is_none());
+    }
+}
+
+#[test_case]
+fn full_queue_refuses_messages() {
+    let queue = Queue::new();
+    let message = Message::new(Duration::from_secs(1), Level::Warn, format_args!("Full"));
+    for _ in 0..QUEUE_SIZE {
+        assert!(queue.push(message));
+    }
+    assert!(!queue.push(message));
+    unsafe { queue.pop() };
+    assert!(queue.push(message));
+}
+
+#[test_case]
+fn long_messages_are_cut_short() {
+    let ten = "éééééééééé";
+    let message = Message::new(
+        Duration::from_secs(0),
+        Level::Info,
+        format_args!("{0}{0}{0}{0}{0}{0}{0}{0}", ten),
+    );
+    assert_eq!(message.text().len(), MESSAGE_SIZE);
+    assert!(message.text().chars().all(|c| c == 'é'));
+}
+
+// end of tests
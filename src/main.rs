@@ -12,10 +12,18 @@ use core::panic::PanicInfo;
 
 use alloc::boxed::Box;
 use blight_os::{
-    hlt_loop,
+    console::{self, Backend},
+    graphics,
+    keyboard::layout,
     memory::BootInfoFrameAllocator,
     println,
-    task::{basic_executor::BasicExecutor, Task},
+    serial::ComPort,
+    shell,
+    task::{
+        executor::Executor,
+        keyboard::{KeyEventStream, LineReader},
+        serial, Task,
+    },
 };
 use bootloader::{entry_point, BootInfo};
 use x86_64::{
@@ -25,9 +33,14 @@ use x86_64::{
 
 entry_point!(kernel_entry);
 
+const FRAMEBUFFER_SIZE: (u16, u16) = (1024, 768);
+
 fn kernel_entry(boot_info: &'static BootInfo) -> ! {
+    // Left off in tests, where the serial port carries the results
+    #[cfg(not(test))]
+    console::set_enabled("serial", true).unwrap();
     blight_os::init();
-    print_banner();
+    let params = blight_os::boot_params::get();
 
     let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
     let mut mapper = unsafe { blight_os::memory::init(physical_offset) };
@@ -36,16 +49,45 @@ fn kernel_entry(boot_info: &'static BootInfo) -> ! {
     blight_os::allocator::init_heap(&mut mapper, &mut frame_allocator)
         .expect("Heap allocation failed.");
 
-    let some_shit_on_the_heap = Box::new(420);
-    let mut executor = BasicExecutor::new();
-    executor.spawn(Task::new(say_hello()));
-    executor.run();
+    if params.console == Backend::Framebuffer {
+        let (width, height) = FRAMEBUFFER_SIZE;
+        match blight_os::framebuffer::init(width, height, &mut mapper, &mut frame_allocator) {
+            Ok(()) => {
+                console::select(Backend::Framebuffer);
+            }
+            Err(err) => log::warn!("Framebuffer unavailable, staying in text mode: {:?}", err),
+        }
+    }
+    print_banner();
+
+    if params.apic {
+        if let Err(err) = blight_os::apic::init(&mut mapper, &mut frame_allocator) {
+            log::warn!("Failed to enable APIC, falling back to PIC: {:?}", err);
+        }
+    }
+    match blight_os::hpet::init(&mut mapper, &mut frame_allocator) {
+        Ok(()) => blight_os::time::calibrate_tsc(),
+        Err(err) => log::warn!("HPET unavailable, using PIT calibrated TSC: {:?}", err),
+    }
 
+    let some_shit_on_the_heap = Box::new(420);
     println!("Thing on the heap: {}", *some_shit_on_the_heap);
 
     #[cfg(test)]
     test_runner_entry();
-    hlt_loop();
+
+    shell::init();
+    let mut executor = Executor::new();
+    executor.spawn(Task::named("hello", say_hello()));
+    let keyboard = LineReader::new(KeyEventStream::new(&layout::US));
+    executor.spawn(Task::named("shell", shell::run(keyboard)));
+    if serial::is_receiving(ComPort::Com1) {
+        executor.spawn(Task::named(
+            "serial console",
+            serial::run_console(ComPort::Com1, shell::PROMPT, shell::run_line),
+        ));
+    }
+    executor.run();
 }
 
 async fn get_name() -> &'static str {
@@ -78,6 +120,9 @@ fn trigger_page_fault() {
 
 #[rustfmt::skip]
 fn print_banner(){
+    if graphics::splash::show() {
+        return;
+    }
     println!("{:^80}", "---------------------------------------------------------");
     println!("{:^80}", "|                                                       |");
     println!("{:^80}", "|               ____  _ _       _     _                 |");
@@ -113,9 +158,11 @@ fn panic(info: &PanicInfo) -> ! {
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("WOuPeR dOopEr. Looks like someone made a wittle little fucky wucky.:");
-    println!("{}", info);
-    hlt_loop();
+    blight_os::emergency_println!(
+        "WOuPeR dOopEr. Looks like someone made a wittle little fucky wucky.:"
+    );
+    blight_os::emergency_println!("{}", info);
+    blight_os::hlt_loop();
 }
 
 #[test_case]
@@ -1,12 +1,23 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use conquer_once::spin::OnceCell;
 use x86_64::{
+    registers::control::Cr3,
     structures::paging::{
-        FrameAllocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame,
-        Size4KiB,
+        mapper::MapToError, FrameAllocator, Mapper, OffsetPageTable, Page, PageTable,
+        PageTableFlags, PhysFrame, Size4KiB,
     },
     PhysAddr, VirtAddr,
 };
 
+/// Virtual address at which the bootloader mapped all of physical memory
+static PHYSICAL_OFFSET: OnceCell<VirtAddr> = OnceCell::uninit();
+
+// Frames given out by, and available to, frame allocators
+static FRAMES_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static FRAMES_USABLE: AtomicUsize = AtomicUsize::new(0);
+
 unsafe fn get_active_lvl4_table(physical_offset: VirtAddr) -> &'static mut PageTable {
     let (table_frame, _) = x86_64::registers::control::Cr3::read();
     let physical = table_frame.start_address().as_u64();
@@ -17,6 +28,9 @@ unsafe fn get_active_lvl4_table(physical_offset: VirtAddr) -> &'static mut PageT
 }
 
 pub unsafe fn init(physical_offset: VirtAddr) -> OffsetPageTable<'static> {
+    PHYSICAL_OFFSET
+        .try_init_once(|| physical_offset)
+        .expect("Memory already initialized");
     let active_table = get_active_lvl4_table(physical_offset);
     OffsetPageTable::new(active_table, physical_offset)
 }
@@ -28,10 +42,12 @@ pub struct BootInfoFrameAllocator {
 
 impl BootInfoFrameAllocator {
     pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
-        BootInfoFrameAllocator {
+        let allocator = BootInfoFrameAllocator {
             memory_map,
             next: 0,
-        }
+        };
+        FRAMES_USABLE.store(allocator.usable_frames().count(), Ordering::Relaxed);
+        allocator
     }
 
     fn usable_frames(&self) -> impl Iterator<Item = PhysFrame> {
@@ -50,10 +66,89 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
         let frame = self.usable_frames().nth(self.next);
         self.next += 1;
+        if frame.is_some() {
+            FRAMES_ALLOCATED.fetch_add(1, Ordering::Relaxed);
+        }
         frame
     }
 }
 
+/// Physical frames as `(allocated, usable)`
+pub fn frame_stats() -> (usize, usize) {
+    (
+        FRAMES_ALLOCATED.load(Ordering::Relaxed),
+        FRAMES_USABLE.load(Ordering::Relaxed),
+    )
+}
+
+/// Physical address `address` is mapped to by the active page tables, or
+/// `None` if it isn't mapped. Only reads the tables, so it can be used without
+/// access to the kernel's mapper
+pub fn translate(address: VirtAddr) -> Option<PhysAddr> {
+    let offset = *PHYSICAL_OFFSET.try_get().ok()?;
+    let (mut frame, _) = Cr3::read();
+    let indexes = [
+        address.p4_index(),
+        address.p3_index(),
+        address.p2_index(),
+        address.p1_index(),
+    ];
+    for (level, &index) in indexes.iter().enumerate() {
+        let table: *const PageTable = (offset + frame.start_address().as_u64()).as_ptr();
+        let table = unsafe { &*table };
+        let entry = &table[index];
+        if !entry.flags().contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        // 1GiB and 2MiB pages end the walk early
+        if entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            let page_size: u64 = match level {
+                1 => 1 << 30,
+                2 => 1 << 21,
+                _ => return None,
+            };
+            return Some(entry.addr() + (address.as_u64() & (page_size - 1)));
+        }
+        frame = PhysFrame::containing_address(entry.addr());
+    }
+    Some(frame.start_address() + u64::from(address.page_offset()))
+}
+
+/// Translate a physical address to its virtual address in the physical memory
+/// mapping set up by the bootloader
+pub fn phys_to_virt(address: PhysAddr) -> VirtAddr {
+    let offset = PHYSICAL_OFFSET.try_get().expect("Memory not initialized");
+    *offset + address.as_u64()
+}
+
+/// Map `size` bytes of memory mapped IO starting at `address` into the
+/// physical memory mapping, with caching disabled.
+///
+/// The bootloader only maps physical memory that's backed by RAM, so device
+/// registers (like the APIC) have to be mapped manually before they can be
+/// accessed through `phys_to_virt`.
+pub fn map_mmio(
+    address: PhysAddr,
+    size: u64,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<VirtAddr, MapToError<Size4KiB>> {
+    let start_frame = PhysFrame::<Size4KiB>::containing_address(address);
+    let end_frame = PhysFrame::<Size4KiB>::containing_address(address + size - 1u64);
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE;
+
+    for frame in PhysFrame::range_inclusive(start_frame, end_frame) {
+        let page = Page::containing_address(phys_to_virt(frame.start_address()));
+        match unsafe { mapper.map_to(page, frame, flags, frame_allocator) } {
+            Ok(flush) => flush.flush(),
+            // Already covered by the physical memory mapping
+            Err(MapToError::PageAlreadyMapped(_)) | Err(MapToError::ParentEntryHugePage) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(phys_to_virt(address))
+}
+
 pub fn create_sample_page(
     page: Page,
     mapper: &mut OffsetPageTable,
@@ -0,0 +1,149 @@
+// First byte of every packet
+const PACKET_LEFT: u8 = 1 << 0;
+const PACKET_RIGHT: u8 = 1 << 1;
+const PACKET_MIDDLE: u8 = 1 << 2;
+/// Always set in the first byte, used to find the start of a packet again
+const PACKET_ALWAYS_ONE: u8 = 1 << 3;
+const PACKET_X_SIGN: u8 = 1 << 4;
+const PACKET_Y_SIGN: u8 = 1 << 5;
+const PACKET_X_OVERFLOW: u8 = 1 << 6;
+const PACKET_Y_OVERFLOW: u8 = 1 << 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseButtons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    /// Horizontal movement, positive to the right
+    pub dx: i16,
+    /// Vertical movement, positive downwards like screen coordinates
+    pub dy: i16,
+    pub buttons: MouseButtons,
+    /// Scroll wheel movement, positive towards the user. Always 0 without an
+    /// IntelliMouse compatible wheel
+    pub scroll: i8,
+}
+
+/// Assembles the bytes sent by a PS/2 mouse into packets. Standard mice send
+/// 3 byte packets, IntelliMouse ones add a fourth byte for the scroll wheel
+pub struct PacketDecoder {
+    packet: [u8; 4],
+    received: usize,
+    length: usize,
+}
+
+impl PacketDecoder {
+    pub const fn new(has_wheel: bool) -> Self {
+        Self {
+            packet: [0; 4],
+            received: 0,
+            length: if has_wheel { 4 } else { 3 },
+        }
+    }
+
+    /// Feed a byte from the mouse, returning an event once a packet is complete
+    pub fn decode(&mut self, byte: u8) -> Option<MouseEvent> {
+        // Drop bytes until something that can start a packet turns up
+        if self.received == 0 && byte & PACKET_ALWAYS_ONE == 0 {
+            return None;
+        }
+        self.packet[self.received] = byte;
+        self.received += 1;
+        if self.received < self.length {
+            return None;
+        }
+        self.received = 0;
+        Some(self.event())
+    }
+
+    fn event(&self) -> MouseEvent {
+        let [flags, x, y, z] = self.packet;
+        // Movement is 9 bit two's complement, with the sign bit in the flags
+        let movement = |value: u8, sign: u8, overflow: u8| {
+            if flags & overflow != 0 {
+                0
+            } else if flags & sign != 0 {
+                value as i16 - 0x100
+            } else {
+                value as i16
+            }
+        };
+        MouseEvent {
+            dx: movement(x, PACKET_X_SIGN, PACKET_X_OVERFLOW),
+            dy: -movement(y, PACKET_Y_SIGN, PACKET_Y_OVERFLOW),
+            buttons: MouseButtons {
+                left: flags & PACKET_LEFT != 0,
+                right: flags & PACKET_RIGHT != 0,
+                middle: flags & PACKET_MIDDLE != 0,
+            },
+            scroll: if self.length == 4 { z as i8 } else { 0 },
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+fn decode_all(decoder: &mut PacketDecoder, bytes: &[u8]) -> Option<MouseEvent> {
+    bytes.iter().filter_map(|byte| decoder.decode(*byte)).last()
+}
+
+#[test_case]
+fn standard_packet_is_decoded() {
+    let mut decoder = PacketDecoder::new(false);
+    assert_eq!(decoder.decode(0b0000_1001), None);
+    assert_eq!(decoder.decode(5), None);
+    let event = decoder.decode(3).unwrap();
+    assert_eq!(event.dx, 5);
+    assert_eq!(event.dy, -3);
+    assert_eq!(
+        event.buttons,
+        MouseButtons {
+            left: true,
+            right: false,
+            middle: false,
+        }
+    );
+    assert_eq!(event.scroll, 0);
+}
+
+#[test_case]
+fn negative_movement_is_sign_extended() {
+    let mut decoder = PacketDecoder::new(false);
+    let event = decode_all(&mut decoder, &[0b0011_1110, 0xfb, 0xfe]).unwrap();
+    assert_eq!(event.dx, -5);
+    assert_eq!(event.dy, 2);
+    assert!(!event.buttons.left && event.buttons.right && event.buttons.middle);
+}
+
+#[test_case]
+fn overflow_is_discarded() {
+    let mut decoder = PacketDecoder::new(false);
+    let event = decode_all(&mut decoder, &[0b1100_1000, 0xff, 0xff]).unwrap();
+    assert_eq!((event.dx, event.dy), (0, 0));
+}
+
+#[test_case]
+fn wheel_packet_is_decoded() {
+    let mut decoder = PacketDecoder::new(true);
+    assert_eq!(decode_all(&mut decoder, &[0b0000_1000, 1, 1]), None);
+    let event = decoder.decode(0xff).unwrap();
+    assert_eq!(event.scroll, -1);
+    assert_eq!((event.dx, event.dy), (1, -1));
+}
+
+#[test_case]
+fn decoder_resynchronises() {
+    let mut decoder = PacketDecoder::new(false);
+    // Bytes without the always-one bit can't start a packet
+    assert_eq!(decoder.decode(0x00), None);
+    assert_eq!(decoder.decode(0x10), None);
+    let event = decode_all(&mut decoder, &[0b0000_1000, 7, 0]).unwrap();
+    assert_eq!((event.dx, event.dy), (7, 0));
+}
+
+// end of tests
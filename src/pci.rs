@@ -0,0 +1,109 @@
+use x86_64::instructions::port::Port;
+
+const CONFIG_ADDRESS: u16 = 0xcf8;
+const CONFIG_DATA: u16 = 0xcfc;
+const CONFIG_ENABLE: u32 = 1 << 31;
+
+// Configuration space offsets
+const VENDOR_DEVICE_ID: u8 = 0x00;
+const HEADER_TYPE: u8 = 0x0e;
+const BAR0: u8 = 0x10;
+
+const HEADER_MULTI_FUNCTION: u8 = 1 << 7;
+/// Vendor ID read back from slots without a device
+const NO_VENDOR: u16 = 0xffff;
+
+/// Mask of the address bits of a memory BAR
+const BAR_MEMORY_ADDRESS: u32 = !0x0f;
+
+/// A function on the PCI bus, accessed through configuration mechanism #1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+}
+
+/// Read the configuration register at `offset`, which is rounded down to
+/// a multiple of 4
+fn read_config(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    let address = CONFIG_ENABLE
+        | (bus as u32) << 16
+        | (device as u32) << 11
+        | (function as u32) << 8
+        | (offset & 0xfc) as u32;
+    // The address and data accesses mustn't be split by another access
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        Port::<u32>::new(CONFIG_ADDRESS).write(address);
+        Port::<u32>::new(CONFIG_DATA).read()
+    })
+}
+
+impl PciDevice {
+    fn probe(bus: u8, device: u8, function: u8) -> Option<PciDevice> {
+        let id = read_config(bus, device, function, VENDOR_DEVICE_ID);
+        let vendor_id = id as u16;
+        if vendor_id == NO_VENDOR {
+            return None;
+        }
+        Some(PciDevice {
+            bus,
+            device,
+            function,
+            vendor_id,
+            device_id: (id >> 16) as u16,
+        })
+    }
+
+    /// Read the configuration register at `offset`
+    pub fn read(&self, offset: u8) -> u32 {
+        read_config(self.bus, self.device, self.function, offset)
+    }
+
+    fn is_multi_function(&self) -> bool {
+        (self.read(HEADER_TYPE) >> 16) as u8 & HEADER_MULTI_FUNCTION != 0
+    }
+
+    /// Physical address a memory BAR points at
+    pub fn memory_bar(&self, index: u8) -> u32 {
+        self.read(BAR0 + index * 4) & BAR_MEMORY_ADDRESS
+    }
+}
+
+/// Every function on every bus, found by brute force scanning
+pub fn devices() -> impl Iterator<Item = PciDevice> {
+    (0..=255u8)
+        .flat_map(|bus| (0..32u8).map(move |device| (bus, device)))
+        .filter_map(|(bus, device)| PciDevice::probe(bus, device, 0))
+        .flat_map(|first| {
+            let functions = if first.is_multi_function() { 8 } else { 1 };
+            (0..functions).filter_map(move |function| match function {
+                0 => Some(first),
+                _ => PciDevice::probe(first.bus, first.device, function),
+            })
+        })
+}
+
+/// First device with the given IDs
+pub fn find(vendor_id: u16, device_id: u16) -> Option<PciDevice> {
+    devices().find(|device| device.vendor_id == vendor_id && device.device_id == device_id)
+}
+
+// Tests
+
+#[test_case]
+fn host_bridge_is_found() {
+    let first = devices().next().expect("No PCI devices");
+    assert_eq!((first.bus, first.device, first.function), (0, 0, 0));
+}
+
+#[test_case]
+fn vga_is_found() {
+    // QEMU's standard VGA card
+    let vga = find(0x1234, 0x1111).expect("No VGA device");
+    assert_ne!(vga.memory_bar(0), 0);
+}
+
+// end of tests
@@ -0,0 +1,70 @@
+use x86_64::instructions::port::Port;
+
+/// Frequency of the oscillator driving the PIT, in Hz
+pub const BASE_FREQUENCY: u32 = 1_193_182;
+
+/// Frequency the system timer interrupt is configured to fire at, regardless
+/// of whether it's driven by the PIT or the local APIC
+pub const TIMER_FREQUENCY: u32 = 100;
+
+/// IRQ line channel 0 is wired to
+pub const TIMER_IRQ: u8 = 0;
+
+const CHANNEL0_PORT: u16 = 0x40;
+const CHANNEL2_PORT: u16 = 0x42;
+const COMMAND_PORT: u16 = 0x43;
+// Keyboard controller port B. Bit 0 gates channel 2, bit 1 connects it to the
+// PC speaker and bit 5 reflects the output of channel 2
+const PORT_B: u16 = 0x61;
+
+/// Program channel 0 (IRQ0) to fire at `frequency` Hz
+pub fn set_frequency(frequency: u32) {
+    let divisor = (BASE_FREQUENCY / frequency).max(1).min(0xffff) as u16;
+    let mut command = Port::<u8>::new(COMMAND_PORT);
+    let mut data = Port::<u8>::new(CHANNEL0_PORT);
+    unsafe {
+        // channel 0, lobyte/hibyte access, mode 3 (square wave)
+        command.write(0b0011_0110);
+        data.write(divisor as u8);
+        data.write((divisor >> 8) as u8);
+    }
+}
+
+/// Busy wait for `ms` milliseconds using channel 2.
+///
+/// Doesn't depend on interrupts, so it can be used to calibrate other timers
+/// before interrupts are set up. Channel 2 can only count 65535 ticks, so
+/// waits longer than ~54ms are split into multiple rounds.
+pub fn sleep_ms(ms: u32) {
+    const MAX_MS: u32 = 50;
+    let mut remaining = ms;
+    while remaining > 0 {
+        let round = remaining.min(MAX_MS);
+        one_shot(BASE_FREQUENCY / 1000 * round);
+        remaining -= round;
+    }
+}
+
+fn one_shot(ticks: u32) {
+    let ticks = ticks.min(0xffff) as u16;
+    let mut port_b = Port::<u8>::new(PORT_B);
+    let mut command = Port::<u8>::new(COMMAND_PORT);
+    let mut data = Port::<u8>::new(CHANNEL2_PORT);
+    unsafe {
+        // Disable the speaker and drop the gate while programming
+        let control = port_b.read() & !0b11;
+        port_b.write(control);
+
+        // channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal count)
+        command.write(0b1011_0000);
+        data.write(ticks as u8);
+        data.write((ticks >> 8) as u8);
+
+        // Raise the gate to start counting, and wait for the output to go high
+        port_b.write(control | 0b1);
+        while port_b.read() & 0b10_0000 == 0 {
+            core::hint::spin_loop();
+        }
+        port_b.write(control);
+    }
+}
@@ -0,0 +1,40 @@
+use x86_64::{
+    instructions::{interrupts, port::Port, tables::lidt},
+    structures::DescriptorTablePointer,
+    VirtAddr,
+};
+
+use crate::{hlt_loop, ps2};
+
+/// Ports emulators power off through without going through ACPI, with the
+/// value to write: QEMU, then Bochs and older QEMU, then VirtualBox
+const SHUTDOWN_PORTS: [(u16, u16); 3] = [(0x604, 0x2000), (0xb004, 0x2000), (0x4004, 0x3400)];
+
+/// Restart the machine through the PS/2 controller, falling back to a triple
+/// fault
+pub fn reboot() -> ! {
+    interrupts::disable();
+    if let Err(err) = ps2::pulse_reset_line() {
+        log::warn!("PS/2 controller didn't reset the CPU: {:?}", err);
+    }
+    // With an empty IDT the breakpoint can't be handled, and neither can the
+    // double fault that follows, so the CPU resets
+    let empty = DescriptorTablePointer {
+        limit: 0,
+        base: VirtAddr::zero(),
+    };
+    unsafe { lidt(&empty) };
+    interrupts::int3();
+    hlt_loop();
+}
+
+/// Power the machine off. Only works in emulators, as real hardware needs the
+/// sleep state from the ACPI DSDT, which takes an AML interpreter to read
+pub fn shutdown() -> ! {
+    interrupts::disable();
+    for &(port, value) in SHUTDOWN_PORTS.iter() {
+        unsafe { Port::<u16>::new(port).write(value) };
+    }
+    log::error!("Failed to power off, halting instead");
+    hlt_loop();
+}
@@ -0,0 +1,323 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use x86_64::instructions::{interrupts::without_interrupts, port::Port};
+
+use crate::keyboard::Modifiers;
+
+const DATA_PORT: u16 = 0x60;
+/// Reads return the status register, writes go to the command register
+const COMMAND_PORT: u16 = 0x64;
+
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+/// The byte in the output buffer came from the second port
+const STATUS_SECOND_OUTPUT: u8 = 1 << 5;
+
+// Controller commands
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_SECOND: u8 = 0xa7;
+const CMD_ENABLE_SECOND: u8 = 0xa8;
+const CMD_TEST_SECOND: u8 = 0xa9;
+const CMD_SELF_TEST: u8 = 0xaa;
+const CMD_TEST_FIRST: u8 = 0xab;
+const CMD_DISABLE_FIRST: u8 = 0xad;
+const CMD_ENABLE_FIRST: u8 = 0xae;
+/// Send the next data byte to the second port instead of the first
+const CMD_WRITE_SECOND: u8 = 0xd4;
+/// Pulse the CPU reset line
+const CMD_PULSE_RESET: u8 = 0xfe;
+
+const CONFIG_FIRST_INTERRUPT: u8 = 1 << 0;
+const CONFIG_SECOND_INTERRUPT: u8 = 1 << 1;
+const CONFIG_SECOND_CLOCK_DISABLED: u8 = 1 << 5;
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+
+const SELF_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+
+// Keyboard commands
+const KBD_SET_LEDS: u8 = 0xed;
+const KBD_SCANCODE_SET: u8 = 0xf0;
+const KBD_ENABLE_SCANNING: u8 = 0xf4;
+const KBD_DISABLE_SCANNING: u8 = 0xf5;
+
+// Mouse commands
+const MOUSE_GET_ID: u8 = 0xf2;
+const MOUSE_SET_SAMPLE_RATE: u8 = 0xf3;
+const MOUSE_ENABLE_REPORTING: u8 = 0xf4;
+const MOUSE_SET_DEFAULTS: u8 = 0xf6;
+
+/// Device ID reported by a mouse with a scroll wheel
+const MOUSE_ID_INTELLIMOUSE: u8 = 3;
+
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+
+pub const LED_SCROLL_LOCK: u8 = 1 << 0;
+pub const LED_NUM_LOCK: u8 = 1 << 1;
+pub const LED_CAPS_LOCK: u8 = 1 << 2;
+
+/// Status register polls before giving up on the controller
+const TIMEOUT: usize = 100_000;
+/// Times a device command is repeated when the device asks for a resend
+const RETRIES: usize = 3;
+
+static SECOND_PORT: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Error {
+    /// The controller didn't accept or answer a byte in time
+    Timeout,
+    /// There is no second port to talk to the mouse through
+    NoSecondPort,
+    SelfTestFailed(u8),
+    PortTestFailed {
+        port: u8,
+        result: u8,
+    },
+    /// The device kept asking for `command` to be resent
+    Resend(u8),
+    UnexpectedResponse(u8),
+}
+
+fn status() -> u8 {
+    unsafe { Port::<u8>::new(COMMAND_PORT).read() }
+}
+
+fn wait_for(flag: u8, set: bool) -> Result<(), Ps2Error> {
+    for _ in 0..TIMEOUT {
+        if (status() & flag != 0) == set {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+    Err(Ps2Error::Timeout)
+}
+
+fn write_command(command: u8) -> Result<(), Ps2Error> {
+    wait_for(STATUS_INPUT_FULL, false)?;
+    unsafe { Port::<u8>::new(COMMAND_PORT).write(command) };
+    Ok(())
+}
+
+fn write_data(value: u8) -> Result<(), Ps2Error> {
+    wait_for(STATUS_INPUT_FULL, false)?;
+    unsafe { Port::<u8>::new(DATA_PORT).write(value) };
+    Ok(())
+}
+
+fn read_response() -> Result<u8, Ps2Error> {
+    wait_for(STATUS_OUTPUT_FULL, true)?;
+    Ok(unsafe { Port::<u8>::new(DATA_PORT).read() })
+}
+
+fn read_data() -> Option<u8> {
+    if status() & STATUS_OUTPUT_FULL != 0 {
+        Some(unsafe { Port::<u8>::new(DATA_PORT).read() })
+    } else {
+        None
+    }
+}
+
+/// Read a byte from the keyboard, if the controller has one. Interrupt
+/// handlers should use this rather than reading the port blindly, as the byte
+/// that raised the interrupt may already have been consumed by polling
+pub fn read_keyboard() -> Option<u8> {
+    if status() & STATUS_SECOND_OUTPUT != 0 {
+        return None;
+    }
+    read_data()
+}
+
+/// Read a byte from the mouse, if the controller has one
+pub fn read_mouse() -> Option<u8> {
+    if status() & STATUS_SECOND_OUTPUT == 0 {
+        return None;
+    }
+    read_data()
+}
+
+fn flush_output() {
+    while read_data().is_some() {}
+}
+
+fn read_config() -> Result<u8, Ps2Error> {
+    write_command(CMD_READ_CONFIG)?;
+    read_response()
+}
+
+fn write_config(config: u8) -> Result<(), Ps2Error> {
+    write_command(CMD_WRITE_CONFIG)?;
+    write_data(config)
+}
+
+#[derive(Clone, Copy)]
+enum Device {
+    Keyboard,
+    Mouse,
+}
+
+/// Send a byte to a device and wait for it to be acknowledged, resending it
+/// if asked to
+fn send(device: Device, value: u8) -> Result<(), Ps2Error> {
+    for _ in 0..RETRIES {
+        if let Device::Mouse = device {
+            write_command(CMD_WRITE_SECOND)?;
+        }
+        write_data(value)?;
+        match read_response()? {
+            ACK => return Ok(()),
+            RESEND => continue,
+            other => return Err(Ps2Error::UnexpectedResponse(other)),
+        }
+    }
+    Err(Ps2Error::Resend(value))
+}
+
+/// Send a device command followed by its argument
+fn command_with_argument(device: Device, command: u8, argument: u8) -> Result<(), Ps2Error> {
+    send(device, command)?;
+    send(device, argument)
+}
+
+/// Reset the i8042 controller to a known state: run its self test, test the
+/// ports and enable the keyboard with interrupts.
+///
+/// The keyboard is switched to scancode set 2 with the controller's
+/// translation enabled, so port 0x60 delivers the set 1 scancodes
+/// `keyboard::ScancodeDecoder` expects. Has to run before the keyboard IRQ is
+/// registered, as the handler would otherwise eat the responses.
+pub fn init() -> Result<(), Ps2Error> {
+    without_interrupts(|| {
+        write_command(CMD_DISABLE_FIRST)?;
+        write_command(CMD_DISABLE_SECOND)?;
+        flush_output();
+
+        let mut config = read_config()?;
+        config &= !(CONFIG_FIRST_INTERRUPT | CONFIG_SECOND_INTERRUPT);
+        write_config(config)?;
+
+        // The self test resets the controller on some hardware, so the
+        // configuration has to be written again afterwards
+        write_command(CMD_SELF_TEST)?;
+        let result = read_response()?;
+        if result != SELF_TEST_PASSED {
+            return Err(Ps2Error::SelfTestFailed(result));
+        }
+        write_config(config)?;
+
+        // The second port's clock is only enabled if it exists
+        write_command(CMD_ENABLE_SECOND)?;
+        let second_port = read_config()? & CONFIG_SECOND_CLOCK_DISABLED == 0;
+        write_command(CMD_DISABLE_SECOND)?;
+
+        write_command(CMD_TEST_FIRST)?;
+        let result = read_response()?;
+        if result != PORT_TEST_PASSED {
+            return Err(Ps2Error::PortTestFailed { port: 1, result });
+        }
+        if second_port {
+            write_command(CMD_TEST_SECOND)?;
+            let result = read_response()?;
+            SECOND_PORT.store(result == PORT_TEST_PASSED, Ordering::Relaxed);
+        }
+
+        write_command(CMD_ENABLE_FIRST)?;
+        write_config(config | CONFIG_FIRST_INTERRUPT | CONFIG_TRANSLATION)?;
+
+        send(Device::Keyboard, KBD_DISABLE_SCANNING)?;
+        command_with_argument(Device::Keyboard, KBD_SCANCODE_SET, 2)?;
+        command_with_argument(Device::Keyboard, KBD_SET_LEDS, 0)?;
+        send(Device::Keyboard, KBD_ENABLE_SCANNING)?;
+        flush_output();
+        Ok(())
+    })
+}
+
+/// Whether the controller has a working second (mouse) port
+pub fn has_second_port() -> bool {
+    SECOND_PORT.load(Ordering::Relaxed)
+}
+
+/// Ask the controller to reset the CPU. Returns if it doesn't
+pub fn pulse_reset_line() -> Result<(), Ps2Error> {
+    write_command(CMD_PULSE_RESET)
+}
+
+/// Set the keyboard LEDs to `leds`, a combination of the `LED_*` flags
+pub fn set_leds(leds: u8) -> Result<(), Ps2Error> {
+    // The ACK would otherwise end up in the scancode queue
+    without_interrupts(|| command_with_argument(Device::Keyboard, KBD_SET_LEDS, leds))
+}
+
+/// Enable the mouse on the second port and turn on data reporting. Tries to
+/// unlock the IntelliMouse scroll wheel, and returns whether that worked and
+/// the mouse will send 4 byte packets
+pub fn init_mouse() -> Result<bool, Ps2Error> {
+    if !has_second_port() {
+        return Err(Ps2Error::NoSecondPort);
+    }
+    without_interrupts(|| {
+        write_command(CMD_ENABLE_SECOND)?;
+        let config = read_config()?;
+        write_config(config | CONFIG_SECOND_INTERRUPT)?;
+
+        send(Device::Mouse, MOUSE_SET_DEFAULTS)?;
+        // This sequence of sample rates is the magic knock that switches an
+        // IntelliMouse from its 3 byte compatibility mode to 4 byte packets
+        for rate in [200, 100, 80].iter() {
+            command_with_argument(Device::Mouse, MOUSE_SET_SAMPLE_RATE, *rate)?;
+        }
+        send(Device::Mouse, MOUSE_GET_ID)?;
+        let has_wheel = read_response()? == MOUSE_ID_INTELLIMOUSE;
+        send(Device::Mouse, MOUSE_ENABLE_REPORTING)?;
+        Ok(has_wheel)
+    })
+}
+
+/// LED flags matching the lock keys in `modifiers`
+pub fn leds_for(modifiers: &Modifiers) -> u8 {
+    let mut leds = 0;
+    if modifiers.scroll_lock {
+        leds |= LED_SCROLL_LOCK;
+    }
+    if modifiers.num_lock {
+        leds |= LED_NUM_LOCK;
+    }
+    if modifiers.caps_lock {
+        leds |= LED_CAPS_LOCK;
+    }
+    leds
+}
+
+// Tests
+
+#[test_case]
+fn leds_follow_locks() {
+    let mut modifiers = Modifiers::default();
+    assert_eq!(leds_for(&modifiers), 0);
+    modifiers.caps_lock = true;
+    modifiers.scroll_lock = true;
+    assert_eq!(leds_for(&modifiers), LED_CAPS_LOCK | LED_SCROLL_LOCK);
+    modifiers.num_lock = true;
+    modifiers.left_shift = true;
+    assert_eq!(
+        leds_for(&modifiers),
+        LED_CAPS_LOCK | LED_NUM_LOCK | LED_SCROLL_LOCK
+    );
+}
+
+#[test_case]
+fn leds_are_acknowledged() {
+    assert_eq!(set_leds(LED_NUM_LOCK), Ok(()));
+    assert_eq!(set_leds(0), Ok(()));
+}
+
+#[test_case]
+fn second_port_is_detected() {
+    // QEMU always emulates a PS/2 mouse
+    assert!(has_second_port());
+}
+
+// end of tests
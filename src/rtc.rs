@@ -0,0 +1,273 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use x86_64::instructions::port::Port;
+
+use crate::interrupts::{self, IrqError};
+
+/// IRQ line of the RTC periodic interrupt
+pub const RTC_IRQ: u8 = 8;
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+// Setting this bit in the address port disables NMIs while the CMOS is accessed
+const NMI_DISABLE: u8 = 0x80;
+/// Register left selected after an access, with NMIs enabled again. Status
+/// register D is read-only, so stray writes to the data port can't hurt it
+const REG_STATUS_D: u8 = 0x0d;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0a;
+const REG_STATUS_B: u8 = 0x0b;
+const REG_STATUS_C: u8 = 0x0c;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+const STATUS_B_PERIODIC_INTERRUPT: u8 = 1 << 6;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const HOUR_PM: u8 = 1 << 7;
+
+static PERIODIC_TICKS: AtomicU64 = AtomicU64::new(0);
+
+// Selecting a register and accessing it has to happen without the interrupt
+// handler selecting another one in between. NMIs stay masked only for the
+// duration of the access
+
+fn read_register(register: u8) -> u8 {
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        let mut address = Port::<u8>::new(CMOS_ADDRESS);
+        address.write(NMI_DISABLE | register);
+        let value = Port::<u8>::new(CMOS_DATA).read();
+        address.write(REG_STATUS_D);
+        value
+    })
+}
+
+fn write_register(register: u8, value: u8) {
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        let mut address = Port::<u8>::new(CMOS_ADDRESS);
+        address.write(NMI_DISABLE | register);
+        Port::<u8>::new(CMOS_DATA).write(value);
+        address.write(REG_STATUS_D);
+    })
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Seconds since 1970-01-01 00:00:00 UTC
+    pub fn unix_timestamp(&self) -> u64 {
+        // Shift the year to start in March, so the leap day is the last day
+        // of the year, then count days since the epoch
+        let (year, month) = if self.month <= 2 {
+            (self.year as i64 - 1, self.month as i64 + 9)
+        } else {
+            (self.year as i64, self.month as i64 - 3)
+        };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+
+        days as u64 * 86400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Raw register values, before any BCD or 12 hour conversion
+#[derive(PartialEq, Eq)]
+struct RawDateTime([u8; 6]);
+
+fn read_raw() -> RawDateTime {
+    while read_register(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+    RawDateTime([
+        read_register(REG_YEAR),
+        read_register(REG_MONTH),
+        read_register(REG_DAY),
+        read_register(REG_HOURS),
+        read_register(REG_MINUTES),
+        read_register(REG_SECONDS),
+    ])
+}
+
+/// Read the current date and time from the RTC.
+///
+/// The registers are read until two reads in a row agree, as an update could
+/// otherwise start halfway through reading them.
+pub fn read() -> DateTime {
+    let mut raw = read_raw();
+    loop {
+        let again = read_raw();
+        if again == raw {
+            break;
+        }
+        raw = again;
+    }
+    let status_b = read_register(REG_STATUS_B);
+    let [year, month, day, hour, minute, second] = raw.0;
+
+    let pm = hour & HOUR_PM != 0;
+    let hour = hour & !HOUR_PM;
+    let convert = |value| {
+        if status_b & STATUS_B_BINARY == 0 {
+            bcd_to_binary(value)
+        } else {
+            value
+        }
+    };
+    let mut hour = convert(hour);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        // 12 hour clock runs 12, 1, ..., 11
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    DateTime {
+        // The century register isn't reliably available, so assume we're
+        // in the 21st century
+        year: 2000 + convert(year) as u16,
+        month: convert(month),
+        day: convert(day),
+        hour,
+        minute: convert(minute),
+        second: convert(second),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcError {
+    /// The periodic interrupt rate isn't between 3 and 15
+    InvalidRate(u8),
+    Irq(IrqError),
+}
+
+impl From<IrqError> for RtcError {
+    fn from(err: IrqError) -> Self {
+        RtcError::Irq(err)
+    }
+}
+
+/// Enable the RTC periodic interrupt on IRQ8, firing at `32768 >> (rate - 1)`
+/// Hz. `rate` must be between 3 (8192 Hz) and 15 (2 Hz)
+pub fn enable_periodic_interrupt(rate: u8) -> Result<(), RtcError> {
+    if !(3..=15).contains(&rate) {
+        return Err(RtcError::InvalidRate(rate));
+    }
+    interrupts::register_irq(RTC_IRQ, rtc_interrupt_handler)?;
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let status_a = read_register(REG_STATUS_A);
+        write_register(REG_STATUS_A, (status_a & 0xf0) | rate);
+        let status_b = read_register(REG_STATUS_B);
+        write_register(REG_STATUS_B, status_b | STATUS_B_PERIODIC_INTERRUPT);
+        // Throw away any interrupt that's already pending
+        read_register(REG_STATUS_C);
+    });
+    Ok(())
+}
+
+/// Number of periodic interrupts received since they were enabled
+pub fn periodic_ticks() -> u64 {
+    PERIODIC_TICKS.load(Ordering::Relaxed)
+}
+
+/// RTC interrupt handler. Register C has to be read, or the RTC won't raise
+/// any more interrupts
+fn rtc_interrupt_handler(_irq: u8) {
+    read_register(REG_STATUS_C);
+    PERIODIC_TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+// Tests
+
+#[test_case]
+fn bcd_is_converted() {
+    assert_eq!(bcd_to_binary(0x59), 59);
+    assert_eq!(bcd_to_binary(0x00), 0);
+    assert_eq!(bcd_to_binary(0x12), 12);
+}
+
+#[test_case]
+fn unix_timestamp_is_correct() {
+    let epoch = DateTime {
+        year: 1970,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+    assert_eq!(epoch.unix_timestamp(), 0);
+
+    let leap_day = DateTime {
+        year: 2000,
+        month: 2,
+        day: 29,
+        hour: 23,
+        minute: 59,
+        second: 59,
+    };
+    assert_eq!(leap_day.unix_timestamp(), 951868799);
+
+    let summer = DateTime {
+        year: 2021,
+        month: 6,
+        day: 15,
+        hour: 12,
+        minute: 0,
+        second: 0,
+    };
+    assert_eq!(summer.unix_timestamp(), 1623758400);
+}
+
+#[test_case]
+fn invalid_rates_are_rejected() {
+    assert_eq!(enable_periodic_interrupt(2), Err(RtcError::InvalidRate(2)));
+    assert_eq!(
+        enable_periodic_interrupt(16),
+        Err(RtcError::InvalidRate(16))
+    );
+}
+
+#[test_case]
+fn rtc_reads_valid_date() {
+    let now = read();
+    assert!(now.year >= 2020);
+    assert!((1..=12).contains(&now.month));
+    assert!((1..=31).contains(&now.day));
+    assert!(now.hour < 24 && now.minute < 60 && now.second < 60);
+}
+
+// end of tests
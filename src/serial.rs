@@ -1,13 +1,221 @@
+use core::fmt;
 use lazy_static::lazy_static;
 use spin::Mutex;
-use uart_16550::SerialPort;
+use x86_64::instructions::port::Port;
+
+pub mod terminal;
+
+// IO port bases of the standard serial ports
+pub const COM1: u16 = 0x3f8;
+pub const COM2: u16 = 0x2f8;
+pub const COM3: u16 = 0x3e8;
+pub const COM4: u16 = 0x2e8;
+
+// Register offsets from the port base
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+/// Writes control the FIFOs, reads identify the pending interrupt
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+/// With the divisor latch set, `DATA` and `INTERRUPT_ENABLE` hold the baud
+/// rate divisor instead
+const LINE_DIVISOR_LATCH: u8 = 1 << 7;
+const LINE_8N1: u8 = 0b11;
+
+const INTERRUPT_RECEIVED_DATA: u8 = 1 << 0;
+
+/// Enable and clear both FIFOs, interrupting as soon as a byte arrives
+const FIFO_ENABLE_CLEAR: u8 = 0x07;
+
+const MODEM_DTR: u8 = 1 << 0;
+const MODEM_RTS: u8 = 1 << 1;
+const MODEM_OUT1: u8 = 1 << 2;
+/// Connects the interrupt line to the PIC on PC compatibles
+const MODEM_OUT2: u8 = 1 << 3;
+/// Transmitted bytes are received back instead of going out on the line
+const MODEM_LOOPBACK: u8 = 1 << 4;
+
+const STATUS_DATA_READY: u8 = 1 << 0;
+const STATUS_TRANSMIT_EMPTY: u8 = 1 << 5;
+
+/// 115200 baud divided by this
+const BAUD_DIVISOR: u16 = 3;
+/// Byte sent to itself in loopback mode to check the port exists
+const PROBE_BYTE: u8 = 0xae;
+/// Line status polls before giving up on sending a byte
+const TIMEOUT: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError {
+    /// Nothing answered at the port's address
+    NotPresent(u16),
+}
+
+/// The four standard PC serial ports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComPort {
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+}
+
+impl ComPort {
+    pub const ALL: [ComPort; 4] = [ComPort::Com1, ComPort::Com2, ComPort::Com3, ComPort::Com4];
+
+    pub fn base(self) -> u16 {
+        match self {
+            ComPort::Com1 => COM1,
+            ComPort::Com2 => COM2,
+            ComPort::Com3 => COM3,
+            ComPort::Com4 => COM4,
+        }
+    }
+
+    /// ISA IRQ line the port interrupts on. COM1 and COM3 share IRQ4, COM2
+    /// and COM4 share IRQ3
+    pub fn irq(self) -> u8 {
+        match self {
+            ComPort::Com1 | ComPort::Com3 => 4,
+            ComPort::Com2 | ComPort::Com4 => 3,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A 16550 UART
+pub struct SerialPort {
+    base: u16,
+}
+
+impl SerialPort {
+    /// # Safety
+    ///
+    /// `base` must be the IO port base of a 16550 compatible UART, or of
+    /// nothing at all
+    pub const unsafe fn new(base: u16) -> SerialPort {
+        SerialPort { base }
+    }
+
+    fn read(&self, register: u16) -> u8 {
+        unsafe { Port::new(self.base + register).read() }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        unsafe { Port::new(self.base + register).write(value) }
+    }
+
+    /// Set the port up for 38400 baud 8N1 with interrupts off, checking
+    /// that the port is there by sending a byte to itself
+    pub fn init(&mut self) -> Result<(), SerialError> {
+        self.write(INTERRUPT_ENABLE, 0);
+        self.write(LINE_CONTROL, LINE_DIVISOR_LATCH);
+        let [low, high] = BAUD_DIVISOR.to_le_bytes();
+        self.write(DATA, low);
+        self.write(INTERRUPT_ENABLE, high);
+        self.write(LINE_CONTROL, LINE_8N1);
+        self.write(FIFO_CONTROL, FIFO_ENABLE_CLEAR);
+
+        self.write(
+            MODEM_CONTROL,
+            MODEM_RTS | MODEM_OUT1 | MODEM_OUT2 | MODEM_LOOPBACK,
+        );
+        self.write(DATA, PROBE_BYTE);
+        let echoed = (0..TIMEOUT).find_map(|_| self.try_receive());
+        self.write(
+            MODEM_CONTROL,
+            MODEM_DTR | MODEM_RTS | MODEM_OUT1 | MODEM_OUT2,
+        );
+        match echoed {
+            Some(PROBE_BYTE) => Ok(()),
+            _ => Err(SerialError::NotPresent(self.base)),
+        }
+    }
+
+    /// Interrupt whenever a byte is received
+    pub fn enable_receive_interrupt(&mut self) {
+        self.write(INTERRUPT_ENABLE, INTERRUPT_RECEIVED_DATA);
+    }
+
+    pub fn disable_interrupts(&mut self) {
+        self.write(INTERRUPT_ENABLE, 0);
+    }
+
+    /// Send everything back to the port itself instead of out on the line
+    pub fn set_loopback(&mut self, enabled: bool) {
+        let control = self.read(MODEM_CONTROL);
+        if enabled {
+            self.write(MODEM_CONTROL, control | MODEM_LOOPBACK);
+        } else {
+            self.write(MODEM_CONTROL, control & !MODEM_LOOPBACK);
+        }
+    }
+
+    /// Send a byte, once the transmitter has room for it
+    pub fn send(&mut self, byte: u8) {
+        for _ in 0..TIMEOUT {
+            if self.read(LINE_STATUS) & STATUS_TRANSMIT_EMPTY != 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        self.write(DATA, byte);
+    }
+
+    /// The next byte received, if there is one waiting
+    pub fn try_receive(&mut self) -> Option<u8> {
+        if self.read(LINE_STATUS) & STATUS_DATA_READY != 0 {
+            Some(self.read(DATA))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.send(byte);
+        }
+        Ok(())
+    }
+}
 
 lazy_static! {
-    pub static ref SERIAL1: Mutex<SerialPort> = {
-        let mut serial_port = unsafe { SerialPort::new(0x3f8) };
-        serial_port.init();
-        Mutex::new(serial_port)
+    /// Every serial port, indexed by `ComPort::index`. Ports that aren't
+    /// there swallow whatever is written to them
+    pub static ref SERIAL_PORTS: [Mutex<SerialPort>; 4] = {
+        let port = |com: ComPort| {
+            let mut serial_port = unsafe { SerialPort::new(com.base()) };
+            let _ = serial_port.init();
+            Mutex::new(serial_port)
+        };
+        [
+            port(ComPort::Com1),
+            port(ComPort::Com2),
+            port(ComPort::Com3),
+            port(ComPort::Com4),
+        ]
     };
+    pub static ref SERIAL1: &'static Mutex<SerialPort> = &SERIAL_PORTS[0];
+}
+
+/// Writes straight to the first serial port, bypassing `SERIAL1` and its
+/// lock. Output can interleave with whatever else is writing to the port, so
+/// it's only meant for when nothing else can be trusted
+pub struct EmergencyWriter;
+
+impl fmt::Write for EmergencyWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // The port was set up when SERIAL1 was, or the firmware left it usable
+        unsafe { SerialPort::new(COM1) }.write_str(s)
+    }
 }
 
 #[doc(hidden)]
@@ -33,3 +241,21 @@ macro_rules! serial_println {
     ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(
         concat!($fmt, "\n"), $($arg)*));
 }
+
+// Tests
+
+#[test_case]
+fn missing_ports_are_detected() {
+    // Only COM1 is attached when running the tests
+    let mut com4 = unsafe { SerialPort::new(COM4) };
+    assert_eq!(com4.init(), Err(SerialError::NotPresent(COM4)));
+}
+
+#[test_case]
+fn ports_share_irq_lines() {
+    assert_eq!(ComPort::Com1.irq(), ComPort::Com3.irq());
+    assert_eq!(ComPort::Com2.irq(), ComPort::Com4.irq());
+    assert_ne!(ComPort::Com1.irq(), ComPort::Com2.irq());
+}
+
+// end of tests
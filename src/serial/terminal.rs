@@ -0,0 +1,218 @@
+use crate::keyboard::{Key, KeyEvent, KeyState, Modifiers};
+
+const ESC: u8 = 0x1b;
+const DEL: u8 = 0x7f;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// After an ESC
+    Escape,
+    /// Inside an `ESC [` control sequence
+    Csi,
+    /// After `ESC O`, which some terminals send for the cursor keys
+    Ss3,
+}
+
+/// Turns the bytes a terminal emulator sends into key presses, as if they
+/// were typed on the PS/2 keyboard. Understands UTF-8, and the VT100 and
+/// xterm sequences for the cursor and editing keys.
+///
+/// Terminals only send presses, and there's no telling which key produced a
+/// character, so text comes out as `Key::Any` with the character alongside.
+pub struct TerminalDecoder {
+    state: State,
+    /// First parameter of the control sequence being read, like the 3 in
+    /// `ESC [ 3 ~`
+    parameter: u16,
+    /// Past the first parameter, whose modifiers are ignored
+    extra_parameters: bool,
+    /// Set after a CR, so the LF of a CRLF isn't taken for a second Enter
+    after_cr: bool,
+    utf8: [u8; 4],
+    utf8_len: usize,
+    utf8_expected: usize,
+}
+
+impl TerminalDecoder {
+    pub const fn new() -> TerminalDecoder {
+        TerminalDecoder {
+            state: State::Ground,
+            parameter: 0,
+            extra_parameters: false,
+            after_cr: false,
+            utf8: [0; 4],
+            utf8_len: 0,
+            utf8_expected: 0,
+        }
+    }
+
+    /// Feed in the next byte. Returns a key press, along with the character
+    /// it types, once a whole one has been read
+    pub fn decode(&mut self, byte: u8) -> Option<(KeyEvent, Option<char>)> {
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        match self.state {
+            State::Ground => self.ground(byte, after_cr),
+            State::Escape => {
+                self.state = match byte {
+                    b'[' => State::Csi,
+                    b'O' => State::Ss3,
+                    // Alt+key and the like aren't supported
+                    _ => State::Ground,
+                };
+                self.parameter = 0;
+                self.extra_parameters = false;
+                if self.state == State::Ground {
+                    press(Key::Esc, None)
+                } else {
+                    None
+                }
+            }
+            State::Csi => match byte {
+                b'0'..=b'9' if !self.extra_parameters => {
+                    let digit = (byte - b'0') as u16;
+                    self.parameter = self.parameter.saturating_mul(10).saturating_add(digit);
+                    None
+                }
+                // Final bytes end the sequence, anything else is a parameter
+                0x40..=0x7e => {
+                    self.state = State::Ground;
+                    let key = match (byte, self.parameter) {
+                        (b'~', 1) | (b'~', 7) => Key::Home,
+                        (b'~', 2) => Key::Insert,
+                        (b'~', 3) => Key::Delete,
+                        (b'~', 4) | (b'~', 8) => Key::End,
+                        (b'~', 5) => Key::PageUp,
+                        (b'~', 6) => Key::PageDown,
+                        (byte, _) => cursor_key(byte)?,
+                    };
+                    press(key, None)
+                }
+                _ => {
+                    self.extra_parameters = true;
+                    None
+                }
+            },
+            State::Ss3 => {
+                self.state = State::Ground;
+                press(cursor_key(byte)?, None)
+            }
+        }
+    }
+
+    fn ground(&mut self, byte: u8, after_cr: bool) -> Option<(KeyEvent, Option<char>)> {
+        match byte {
+            ESC => {
+                self.state = State::Escape;
+                None
+            }
+            b'\n' if after_cr => None,
+            b'\r' | b'\n' => press(Key::Enter, Some('\n')),
+            DEL | 0x08 => press(Key::Backspace, Some('\x08')),
+            b'\t' => press(Key::Tab, Some('\t')),
+            0x00..=0x7f => press(Key::Any, Some(byte as char)),
+            _ => self.utf8(byte),
+        }
+    }
+
+    /// Collect the bytes of a multi-byte character. Malformed ones are
+    /// dropped
+    fn utf8(&mut self, byte: u8) -> Option<(KeyEvent, Option<char>)> {
+        let expected = match byte {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 0,
+        };
+        if expected > 0 {
+            self.utf8_expected = expected;
+            self.utf8_len = 0;
+        } else if self.utf8_len == 0 || self.utf8_len >= self.utf8_expected {
+            // Continuation byte without a start
+            return None;
+        }
+        self.utf8[self.utf8_len] = byte;
+        self.utf8_len += 1;
+        if self.utf8_len < self.utf8_expected {
+            return None;
+        }
+        let c = core::str::from_utf8(&self.utf8[..self.utf8_len])
+            .ok()?
+            .chars()
+            .next()?;
+        press(Key::Any, Some(c))
+    }
+}
+
+impl Default for TerminalDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Key for the last byte of `ESC [ A` and friends
+fn cursor_key(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        _ => None,
+    }
+}
+
+fn press(key: Key, character: Option<char>) -> Option<(KeyEvent, Option<char>)> {
+    let event = KeyEvent {
+        key,
+        state: KeyState::Down,
+        modifiers: Modifiers::default(),
+    };
+    Some((event, character))
+}
+
+// Tests
+
+#[cfg(test)]
+fn decode_all(bytes: &[u8]) -> ([Option<(Key, Option<char>)>; 4], usize) {
+    let mut decoder = TerminalDecoder::new();
+    let mut presses = [None; 4];
+    let mut count = 0;
+    for &byte in bytes {
+        if let Some((event, character)) = decoder.decode(byte) {
+            presses[count] = Some((event.key, character));
+            count += 1;
+        }
+    }
+    (presses, count)
+}
+
+#[test_case]
+fn text_is_typed() {
+    let (presses, count) = decode_all("aé\r\n".as_bytes());
+    assert_eq!(count, 3);
+    assert_eq!(presses[0], Some((Key::Any, Some('a'))));
+    assert_eq!(presses[1], Some((Key::Any, Some('é'))));
+    assert_eq!(presses[2], Some((Key::Enter, Some('\n'))));
+}
+
+#[test_case]
+fn editing_keys_are_decoded() {
+    let (presses, count) = decode_all(b"\x1b[D\x1bOH\x1b[3~\x7f");
+    assert_eq!(count, 4);
+    assert_eq!(presses[0], Some((Key::Left, None)));
+    assert_eq!(presses[1], Some((Key::Home, None)));
+    assert_eq!(presses[2], Some((Key::Delete, None)));
+    assert_eq!(presses[3], Some((Key::Backspace, Some('\x08'))));
+}
+
+#[test_case]
+fn modifiers_are_ignored() {
+    let (presses, count) = decode_all(b"\x1b[1;5C\x1b[3;2~");
+    assert_eq!(count, 2);
+    assert_eq!(presses[0], Some((Key::Right, None)));
+    assert_eq!(presses[1], Some((Key::Delete, None)));
+}
+
+// end of tests
@@ -0,0 +1,105 @@
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use crate::{println, task::keyboard::LineReader};
+
+mod builtins;
+
+/// Most commands that can be registered at once
+pub const MAX_COMMANDS: usize = 32;
+pub const PROMPT: &str = "blight> ";
+
+/// Runs a command, given the words typed after its name
+pub type CommandFn = fn(args: &[&str]);
+
+#[derive(Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    /// How to call it, like `pt <address>`
+    pub usage: &'static str,
+    /// One line description for `help`
+    pub help: &'static str,
+    pub run: CommandFn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellError {
+    Full,
+    AlreadyRegistered,
+    UnknownCommand,
+}
+
+// Only used from tasks, never from interrupt handlers
+static COMMANDS: Mutex<[Option<Command>; MAX_COMMANDS]> = Mutex::new([None; MAX_COMMANDS]);
+
+/// Register the built-in commands. Does nothing if they're already registered
+pub fn init() {
+    for command in builtins::BUILTINS.iter() {
+        match register(*command) {
+            Ok(()) | Err(ShellError::AlreadyRegistered) => {}
+            Err(err) => log::warn!("Failed to register {}: {:?}", command.name, err),
+        }
+    }
+}
+
+/// Make `command` available in the shell
+pub fn register(command: Command) -> Result<(), ShellError> {
+    let mut commands = COMMANDS.lock();
+    if find_in(&commands[..], command.name).is_some() {
+        return Err(ShellError::AlreadyRegistered);
+    }
+    let slot = commands.iter_mut().find(|slot| slot.is_none());
+    *slot.ok_or(ShellError::Full)? = Some(command);
+    Ok(())
+}
+
+fn find_in(commands: &[Option<Command>], name: &str) -> Option<Command> {
+    commands
+        .iter()
+        .flatten()
+        .find(|command| command.name == name)
+        .copied()
+}
+
+/// The command called `name`
+pub fn find(name: &str) -> Option<Command> {
+    find_in(&COMMANDS.lock()[..], name)
+}
+
+/// Call `f` with every registered command, in the order they were registered
+pub fn for_each_command(mut f: impl FnMut(&Command)) {
+    // Copied so commands can be registered from `f`
+    let commands = *COMMANDS.lock();
+    for command in commands.iter().flatten() {
+        f(command);
+    }
+}
+
+/// Run the command on `line`. Blank lines do nothing
+pub fn execute(line: &str) -> Result<(), ShellError> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (name, args) = match words.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    let command = find(name).ok_or(ShellError::UnknownCommand)?;
+    (command.run)(args);
+    Ok(())
+}
+
+/// Run the command on `line`, complaining if there's no such command
+pub fn run_line(line: &str) {
+    if execute(line) == Err(ShellError::UnknownCommand) {
+        let name = line.split_whitespace().next().unwrap_or("");
+        println!("{}: command not found", name);
+    }
+}
+
+/// Read commands from the keyboard and run them, forever
+pub async fn run(mut reader: LineReader) {
+    loop {
+        reader.write(PROMPT);
+        let line = reader.read_line().await;
+        run_line(&line);
+    }
+}
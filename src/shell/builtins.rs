@@ -0,0 +1,172 @@
+use alloc::string::String;
+use x86_64::VirtAddr;
+
+use super::{for_each_command, Command};
+use crate::{
+    allocator, console, interrupts, logger, memory, power, print, println, task::executor, time,
+};
+
+pub(super) const BUILTINS: [Command; 11] = [
+    Command {
+        name: "help",
+        usage: "help",
+        help: "List the available commands",
+        run: help,
+    },
+    Command {
+        name: "clear",
+        usage: "clear",
+        help: "Clear the screen",
+        run: clear,
+    },
+    Command {
+        name: "echo",
+        usage: "echo <text>...",
+        help: "Print the arguments",
+        run: echo,
+    },
+    Command {
+        name: "mem",
+        usage: "mem",
+        help: "Show heap and physical memory usage",
+        run: mem,
+    },
+    Command {
+        name: "uptime",
+        usage: "uptime",
+        help: "Show how long the kernel has been running",
+        run: uptime,
+    },
+    Command {
+        name: "tasks",
+        usage: "tasks",
+        help: "List the running tasks",
+        run: tasks,
+    },
+    Command {
+        name: "irqs",
+        usage: "irqs",
+        help: "Show how often each interrupt has fired",
+        run: irqs,
+    },
+    Command {
+        name: "dmesg",
+        usage: "dmesg",
+        help: "Print the kernel log",
+        run: dmesg,
+    },
+    Command {
+        name: "pt",
+        usage: "pt <address>",
+        help: "Translate a virtual address through the page tables",
+        run: pt,
+    },
+    Command {
+        name: "reboot",
+        usage: "reboot",
+        help: "Restart the machine",
+        run: reboot,
+    },
+    Command {
+        name: "shutdown",
+        usage: "shutdown",
+        help: "Power the machine off",
+        run: shutdown,
+    },
+];
+
+fn help(_args: &[&str]) {
+    for_each_command(|command| println!("  {:<16}{}", command.usage, command.help));
+}
+
+fn clear(_args: &[&str]) {
+    console::clear();
+}
+
+fn echo(args: &[&str]) {
+    println!("{}", args.join(" "));
+}
+
+fn mem(_args: &[&str]) {
+    let heap_size = allocator::heap_size();
+    let (allocated, usable) = memory::frame_stats();
+    match allocator::free_space() {
+        Some(free) => println!(
+            "Heap:   {:>8} of {:>8} bytes used",
+            heap_size - free,
+            heap_size
+        ),
+        None => println!(
+            "Heap:   {:>8} bytes, {:?} allocator",
+            heap_size,
+            allocator::allocator_kind()
+        ),
+    }
+    println!(
+        "Frames: {:>8} of {:>8} allocated ({} KiB usable)",
+        allocated,
+        usable,
+        usable * 4
+    );
+}
+
+fn uptime(_args: &[&str]) {
+    let uptime = time::uptime();
+    let seconds = uptime.as_secs();
+    println!(
+        "Up {}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        uptime.subsec_millis()
+    );
+}
+
+fn tasks(_args: &[&str]) {
+    println!("{:>4}  NAME", "ID");
+    for (id, name) in executor::running_tasks() {
+        println!("{:>4}  {}", id.as_u64(), name);
+    }
+}
+
+fn irqs(_args: &[&str]) {
+    let mut table = String::new();
+    interrupts::write_interrupt_table(&mut table).unwrap();
+    print!("{}", table);
+}
+
+fn dmesg(_args: &[&str]) {
+    print!("{}", logger::dmesg());
+}
+
+fn pt(args: &[&str]) {
+    let address = match args {
+        [address] => parse_address(address),
+        _ => None,
+    };
+    let address = match address {
+        Some(address) => address,
+        None => return println!("Usage: pt <address>"),
+    };
+    match memory::translate(address) {
+        Some(physical) => println!("{:#x} -> {:#x}", address, physical),
+        None => println!("{:#x} is not mapped", address),
+    }
+}
+
+/// Parse a hexadecimal address with a `0x` prefix, or a decimal one without
+fn parse_address(text: &str) -> Option<VirtAddr> {
+    let address = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    VirtAddr::try_new(address).ok()
+}
+
+fn reboot(_args: &[&str]) {
+    power::reboot();
+}
+
+fn shutdown(_args: &[&str]) {
+    power::shutdown();
+}
@@ -3,6 +3,7 @@ use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 use alloc::collections::VecDeque;
 
 use super::Task;
+use crate::logger;
 
 pub struct BasicExecutor {
     task_queue: VecDeque<Task>,
@@ -20,6 +21,7 @@ impl BasicExecutor {
 
     pub fn run(&mut self) {
         while let Some(mut task) = self.task_queue.pop_front() {
+            logger::drain();
             let waker = dummy_waker();
             let mut context = Context::from_waker(&waker);
             match task.poll(&mut context) {
@@ -1,12 +1,21 @@
 use core::task::{Context, Poll, Waker};
 
-use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
+use alloc::{collections::BTreeMap, sync::Arc, task::Wake, vec::Vec};
 use crossbeam_queue::ArrayQueue;
-use x86_64::instructions::hlt;
-
-use crate::interrupts;
+use spin::Mutex;
 
 use super::{Task, TaskId};
+use crate::logger;
+
+/// Tasks spawned on any executor that haven't finished yet. Never locked from
+/// interrupt handlers
+static RUNNING: Mutex<Vec<(TaskId, &'static str)>> = Mutex::new(Vec::new());
+
+/// ID and name of every task that hasn't finished, in the order they were
+/// spawned
+pub fn running_tasks() -> Vec<(TaskId, &'static str)> {
+    RUNNING.lock().clone()
+}
 
 pub struct Executor {
     tasks: BTreeMap<TaskId, Task>,
@@ -26,6 +35,8 @@ impl Executor {
     pub fn run(&mut self) -> ! {
         loop {
             self.run_ready_tasks();
+            // Messages logged by interrupt handlers that woke the tasks
+            logger::drain();
             self.sleep_if_idle();
         }
     }
@@ -41,10 +52,11 @@ impl Executor {
     }
 
     pub fn spawn(&mut self, task: Task) {
-        let id = task.id;
+        let (id, name) = (task.id, task.name);
         if self.tasks.insert(id, task).is_some() {
             panic!("Another task with provided ID already exists");
         }
+        RUNNING.lock().push((id, name));
         self.task_queue.push(id).expect("Task queue is full");
     }
 
@@ -69,6 +81,7 @@ impl Executor {
                 Poll::Ready(()) => {
                     tasks.remove(&task_id);
                     waker_cache.remove(&task_id);
+                    RUNNING.lock().retain(|(id, _)| *id != task_id);
                 }
                 Poll::Pending => {}
             }
@@ -76,6 +89,12 @@ impl Executor {
     }
 }
 
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 struct TaskWaker {
     task_id: TaskId,
     task_queue: Arc<ArrayQueue<TaskId>>,
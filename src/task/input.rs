@@ -0,0 +1,142 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+use crossbeam_queue::ArrayQueue;
+use futures_util::{stream::Stream, task::AtomicWaker};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+/// Queue capacity used by `InputBuffer::subscribe`
+pub const DEFAULT_CAPACITY: usize = 128;
+
+struct Subscriber<T> {
+    queue: ArrayQueue<T>,
+    waker: AtomicWaker,
+    dropped: AtomicU64,
+}
+
+/// Broadcasts input events from interrupt handlers to any number of
+/// subscribers, each with its own bounded queue.
+///
+/// Pushing never allocates, blocks or prints, so it's safe to call from an
+/// interrupt handler. Events that don't fit in a subscriber's queue are
+/// dropped and counted instead.
+pub struct InputBuffer<T> {
+    // Locked by `push` from interrupt handlers, so it must only be locked
+    // with interrupts disabled anywhere else
+    subscribers: Mutex<Vec<Arc<Subscriber<T>>>>,
+    dropped: AtomicU64,
+}
+
+impl<T> InputBuffer<T> {
+    pub const fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<T> Default for InputBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy> InputBuffer<T> {
+    /// Hand `event` to every subscriber
+    pub fn push(&self, event: T) {
+        for subscriber in self.subscribers.lock().iter() {
+            if subscriber.queue.push(event).is_ok() {
+                subscriber.waker.wake();
+            } else {
+                subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn subscribe(&'static self) -> Subscription<T> {
+        self.subscribe_with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Start receiving every event pushed from now on. Up to `capacity`
+    /// events are kept until they're read
+    pub fn subscribe_with_capacity(&'static self, capacity: usize) -> Subscription<T> {
+        let subscriber = Arc::new(Subscriber {
+            queue: ArrayQueue::new(capacity),
+            waker: AtomicWaker::new(),
+            dropped: AtomicU64::new(0),
+        });
+        without_interrupts(|| self.subscribers.lock().push(subscriber.clone()));
+        Subscription {
+            buffer: self,
+            subscriber,
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        without_interrupts(|| self.subscribers.lock().len())
+    }
+
+    /// Events dropped because a subscriber's queue was full, over all
+    /// subscribers
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Stream of the events pushed to an `InputBuffer`. Unsubscribes when dropped
+pub struct Subscription<T: 'static> {
+    buffer: &'static InputBuffer<T>,
+    subscriber: Arc<Subscriber<T>>,
+}
+
+impl<T: 'static> Subscription<T> {
+    /// Take the next event without waiting
+    pub fn try_next(&self) -> Option<T> {
+        self.subscriber.queue.pop().ok()
+    }
+
+    /// Events this subscription missed because its queue was full
+    pub fn dropped(&self) -> u64 {
+        self.subscriber.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.subscriber.queue.capacity()
+    }
+}
+
+impl<T: 'static> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if let Some(event) = self.try_next() {
+            return Poll::Ready(Some(event));
+        }
+
+        self.subscriber.waker.register(&cx.waker());
+        match self.try_next() {
+            Some(event) => {
+                self.subscriber.waker.take();
+                Poll::Ready(Some(event))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T: 'static> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        without_interrupts(|| {
+            self.buffer
+                .subscribers
+                .lock()
+                .retain(|subscriber| !Arc::ptr_eq(subscriber, &self.subscriber))
+        });
+    }
+}
@@ -1,36 +1,66 @@
-use conquer_once::spin::OnceCell;
-use core::task::Poll;
-use crossbeam_queue::ArrayQueue;
-use futures_util::{task::AtomicWaker, Stream};
+use alloc::string::String;
+use core::{pin::Pin, task::Poll};
+use futures_util::stream::{Stream, StreamExt};
+use x86_64::instructions::interrupts::without_interrupts;
 
-static WAKER: AtomicWaker = AtomicWaker::new();
+use super::{
+    input::{InputBuffer, Subscription},
+    line_editor::LineEditor,
+};
+use crate::{
+    console, interrupts,
+    keyboard::{layout::Layout, KeyEvent, Keyboard},
+    ps2, vga_buffer,
+};
 
-use crate::println;
+/// IRQ line of the PS/2 keyboard
+pub const KEYBOARD_IRQ: u8 = 1;
 
-static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+/// Raw scancodes received from the keyboard
+pub static SCANCODES: InputBuffer<u8> = InputBuffer::new();
 
 pub(crate) fn add_scancode(scancode: u8) {
-    if let Ok(queue) = SCANCODE_QUEUE.try_get() {
-        if let Err(_) = queue.push(scancode) {
-            println!("WARNING. Queue full");
-        } else {
-            WAKER.wake();
-        }
-    } else {
-        println!("WARNING! Queue not initialized");
-    }
+    SCANCODES.push(scancode);
+}
+
+/// Install the keyboard interrupt handler
+pub fn init() {
+    interrupts::register_irq(KEYBOARD_IRQ, keyboard_interrupt_handler)
+        .expect("Failed to register keyboard IRQ");
 }
 
+/// Keyboard interrupt handler
+fn keyboard_interrupt_handler(_irq: u8) {
+    let scancode = match ps2::read_keyboard() {
+        Some(scancode) => scancode,
+        None => return,
+    };
+    add_scancode(scancode);
+}
+
+/// Stream of raw scancodes. Every stream receives all scancodes from the
+/// moment it's created
 pub struct ScancodeStream {
-    _private: (),
+    subscription: Subscription<u8>,
 }
 
 impl ScancodeStream {
     pub fn new() -> Self {
-        SCANCODE_QUEUE
-            .try_init_once(|| ArrayQueue::new(150))
-            .expect("Only init queue once");
-        Self { _private: () }
+        Self {
+            subscription: SCANCODES.subscribe(),
+        }
+    }
+
+    /// Create a stream that can hold `capacity` scancodes before dropping any
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            subscription: SCANCODES.subscribe_with_capacity(capacity),
+        }
+    }
+
+    /// Scancodes this stream missed because it wasn't read fast enough
+    pub fn dropped(&self) -> u64 {
+        self.subscription.dropped()
     }
 }
 
@@ -38,22 +68,145 @@ impl Stream for ScancodeStream {
     type Item = u8;
 
     fn poll_next(
-        self: core::pin::Pin<&mut Self>,
+        mut self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.subscription.poll_next_unpin(cx)
+    }
+}
+
+/// Stream of decoded key presses and releases. Keys used by the console itself,
+/// like Shift+PageUp to scroll back, are not passed on
+pub struct KeyEventStream {
+    scancodes: ScancodeStream,
+    keyboard: Keyboard,
+    /// LED flags last sent to the keyboard
+    leds: u8,
+}
+
+impl KeyEventStream {
+    pub fn new(layout: &'static Layout) -> Self {
+        Self {
+            scancodes: ScancodeStream::new(),
+            keyboard: Keyboard::new(layout),
+            leds: 0,
+        }
+    }
+
+    pub fn keyboard(&self) -> &Keyboard {
+        &self.keyboard
+    }
+
+    pub fn keyboard_mut(&mut self) -> &mut Keyboard {
+        &mut self.keyboard
+    }
+
+    /// Make the keyboard LEDs follow the lock keys
+    fn update_leds(&mut self) {
+        let leds = ps2::leds_for(&self.keyboard.modifiers());
+        if leds != self.leds {
+            // Keep the old state on failure, so the next event retries
+            if ps2::set_leds(leds).is_ok() {
+                self.leds = leds;
+            }
+        }
+    }
+}
+
+impl Stream for KeyEventStream {
+    type Item = KeyEvent;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        // Prefix bytes of multi-byte sequences don't produce an event, so
+        // keep going until one does or the queue runs dry
+        loop {
+            match self.scancodes.poll_next_unpin(cx) {
+                Poll::Ready(Some(scancode)) => {
+                    if let Some(event) = self.keyboard.process_scancode(scancode) {
+                        self.update_leds();
+                        if !vga_buffer::handle_key(&event) {
+                            return Poll::Ready(Some(event));
+                        }
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Stream of typed characters, translated through the keyboard layout
+pub struct CharStream {
+    events: KeyEventStream,
+}
+
+impl CharStream {
+    pub fn new(events: KeyEventStream) -> Self {
+        Self { events }
+    }
+}
+
+impl Stream for CharStream {
+    type Item = char;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
         cx: &mut core::task::Context<'_>,
-    ) -> core::task::Poll<Option<Self::Item>> {
-        let queue = SCANCODE_QUEUE.try_get().expect("Stream not initialized");
+    ) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.events.poll_next_unpin(cx) {
+                Poll::Ready(Some(event)) => {
+                    if let Some(character) = self.events.keyboard.translate(&event) {
+                        return Poll::Ready(Some(character));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
 
-        if let Ok(scancode) = queue.pop() {
-            return Poll::Ready(Some(scancode));
+/// Reads whole lines from the keyboard, echoing them to the screen
+pub struct LineReader {
+    events: KeyEventStream,
+    editor: LineEditor,
+}
+
+impl LineReader {
+    pub fn new(events: KeyEventStream) -> Self {
+        Self {
+            events,
+            editor: LineEditor::new(),
         }
+    }
+
+    pub fn editor(&self) -> &LineEditor {
+        &self.editor
+    }
+
+    /// Write `s` to the screen only, so it doesn't reach the serial console
+    pub fn write(&self, s: &str) {
+        without_interrupts(|| console::screen().write_str(s));
+    }
 
-        WAKER.register(&cx.waker());
-        match queue.pop() {
-            Ok(scancode) => {
-                WAKER.take();
-                Poll::Ready(Some(scancode))
+    /// Wait for a line to be entered. Supports Backspace, Delete, moving the
+    /// cursor with Left/Right/Home/End and recalling earlier lines with Up/Down
+    pub async fn read_line(&mut self) -> String {
+        let mut echo = String::new();
+        while let Some(event) = self.events.next().await {
+            let character = self.events.keyboard.translate(&event);
+            let line = self.editor.handle(&event, character, &mut echo);
+            self.write(&echo);
+            echo.clear();
+            if let Some(line) = line {
+                return line;
             }
-            Err(crossbeam_queue::PopError) => Poll::Pending,
         }
+        self.editor.line()
     }
 }
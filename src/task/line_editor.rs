@@ -0,0 +1,176 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::keyboard::{Key, KeyEvent, KeyState};
+
+/// Number of previously entered lines kept for recall with Up/Down
+pub const HISTORY_SIZE: usize = 32;
+
+/// Moves the VGA cursor one column back without erasing anything
+const CURSOR_BACK: char = '\x08';
+
+/// Editable input line with a cursor and a history of previous lines.
+///
+/// The editor doesn't print anything itself. Every key press appends the
+/// characters needed to update an echoed copy of the line to `echo`, using
+/// `\x08` to move the cursor back.
+pub struct LineEditor {
+    line: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    /// Entry of `history` currently shown, `history.len()` for the new line
+    history_index: usize,
+    /// The new line, stashed away while browsing the history
+    pending: Vec<char>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Contents of the line being edited
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// Cursor position, in characters from the start of the line
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Previously entered lines, oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Apply a key event. `character` is the event translated by the keyboard
+    /// layout. Returns the finished line once Enter is pressed
+    pub fn handle(
+        &mut self,
+        event: &KeyEvent,
+        character: Option<char>,
+        echo: &mut String,
+    ) -> Option<String> {
+        if event.state == KeyState::Up {
+            return None;
+        }
+        match event.key {
+            Key::Left => self.move_to(self.cursor.saturating_sub(1), echo),
+            Key::Right => self.move_to(self.cursor + 1, echo),
+            Key::Home => self.move_to(0, echo),
+            Key::End => self.move_to(self.line.len(), echo),
+            Key::Up => self.history_previous(echo),
+            Key::Down => self.history_next(echo),
+            Key::Delete => self.delete(echo),
+            _ => match character {
+                Some('\n') => return Some(self.finish(echo)),
+                Some('\x08') => {
+                    if self.cursor > 0 {
+                        self.move_to(self.cursor - 1, echo);
+                        self.delete(echo);
+                    }
+                }
+                Some(c) if !c.is_control() => self.insert(c, echo),
+                _ => {}
+            },
+        }
+        None
+    }
+
+    fn move_to(&mut self, position: usize, echo: &mut String) {
+        let position = position.min(self.line.len());
+        while self.cursor > position {
+            echo.push(CURSOR_BACK);
+            self.cursor -= 1;
+        }
+        while self.cursor < position {
+            echo.push(self.line[self.cursor]);
+            self.cursor += 1;
+        }
+    }
+
+    /// Reprint everything after the cursor, blank out `erased` characters
+    /// that used to follow it, and move back to the cursor
+    fn redraw_tail(&self, erased: usize, echo: &mut String) {
+        echo.extend(&self.line[self.cursor..]);
+        echo.extend(core::iter::repeat(' ').take(erased));
+        let moved = self.line.len() - self.cursor + erased;
+        echo.extend(core::iter::repeat(CURSOR_BACK).take(moved));
+    }
+
+    fn insert(&mut self, c: char, echo: &mut String) {
+        self.line.insert(self.cursor, c);
+        self.cursor += 1;
+        echo.push(c);
+        self.redraw_tail(0, echo);
+    }
+
+    fn delete(&mut self, echo: &mut String) {
+        if self.cursor < self.line.len() {
+            self.line.remove(self.cursor);
+            self.redraw_tail(1, echo);
+        }
+    }
+
+    /// Replace the whole line, leaving the cursor at its end
+    fn replace(&mut self, line: Vec<char>, echo: &mut String) {
+        self.move_to(0, echo);
+        let erased = self.line.len().saturating_sub(line.len());
+        self.line = line;
+        self.cursor = self.line.len();
+        echo.extend(&self.line);
+        echo.extend(core::iter::repeat(' ').take(erased));
+        echo.extend(core::iter::repeat(CURSOR_BACK).take(erased));
+    }
+
+    fn history_previous(&mut self, echo: &mut String) {
+        if self.history_index == 0 {
+            return;
+        }
+        if self.history_index == self.history.len() {
+            self.pending = self.line.clone();
+        }
+        self.history_index -= 1;
+        let line = self.history[self.history_index].chars().collect();
+        self.replace(line, echo);
+    }
+
+    fn history_next(&mut self, echo: &mut String) {
+        if self.history_index == self.history.len() {
+            return;
+        }
+        self.history_index += 1;
+        let line = match self.history.get(self.history_index) {
+            Some(entry) => entry.chars().collect(),
+            None => core::mem::take(&mut self.pending),
+        };
+        self.replace(line, echo);
+    }
+
+    fn finish(&mut self, echo: &mut String) -> String {
+        echo.push('\n');
+        let line: String = self.line.drain(..).collect();
+        self.cursor = 0;
+        self.pending.clear();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            if self.history.len() == HISTORY_SIZE {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        self.history_index = self.history.len();
+        line
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
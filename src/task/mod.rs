@@ -2,23 +2,60 @@ use alloc::boxed::Box;
 use core::{
     future::Future,
     pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
     task::{Context, Poll},
 };
 
 pub mod basic_executor;
+pub mod executor;
+pub mod input;
 pub mod keyboard;
+pub mod line_editor;
+pub mod mouse;
+pub mod serial;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
 
 pub struct Task {
+    id: TaskId,
+    name: &'static str,
     future: Pin<Box<dyn Future<Output = ()>>>,
 }
 
 impl Task {
     pub fn new(future: impl Future<Output = ()> + 'static) -> Task {
+        Task::named("task", future)
+    }
+
+    /// Create a task that's listed as `name`
+    pub fn named(name: &'static str, future: impl Future<Output = ()> + 'static) -> Task {
         Task {
+            id: TaskId::new(),
+            name,
             future: Box::pin(future),
         }
     }
 
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
     pub fn poll(&mut self, cx: &mut Context) -> Poll<()> {
         self.future.as_mut().poll(cx)
     }
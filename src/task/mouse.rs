@@ -0,0 +1,103 @@
+use core::{
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::Poll,
+};
+use futures_util::stream::{Stream, StreamExt};
+use spin::Mutex;
+
+use super::input::{InputBuffer, Subscription};
+use crate::{
+    interrupts::{self, IrqError},
+    mouse::{MouseEvent, PacketDecoder},
+    ps2::{self, Ps2Error},
+};
+
+/// IRQ line of the PS/2 mouse
+pub const MOUSE_IRQ: u8 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseInputError {
+    Ps2(Ps2Error),
+    Irq(IrqError),
+}
+
+impl From<Ps2Error> for MouseInputError {
+    fn from(err: Ps2Error) -> Self {
+        MouseInputError::Ps2(err)
+    }
+}
+
+impl From<IrqError> for MouseInputError {
+    fn from(err: IrqError) -> Self {
+        MouseInputError::Irq(err)
+    }
+}
+
+/// Decoded mouse packets
+pub static MOUSE_EVENTS: InputBuffer<MouseEvent> = InputBuffer::new();
+/// Only locked from the interrupt handler, and from `init` with interrupts
+/// disabled
+static DECODER: Mutex<PacketDecoder> = Mutex::new(PacketDecoder::new(false));
+/// Set once the interrupt handler is installed
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enable the mouse, then install its interrupt handler. Does nothing if it's
+/// already enabled
+pub fn init() -> Result<(), MouseInputError> {
+    if ENABLED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let has_wheel = ps2::init_mouse()?;
+        *DECODER.lock() = PacketDecoder::new(has_wheel);
+        interrupts::register_irq(MOUSE_IRQ, mouse_interrupt_handler)?;
+        ENABLED.store(true, Ordering::SeqCst);
+        Ok(())
+    })
+}
+
+/// Mouse interrupt handler
+fn mouse_interrupt_handler(_irq: u8) {
+    if let Some(byte) = ps2::read_mouse() {
+        if let Some(event) = DECODER.lock().decode(byte) {
+            MOUSE_EVENTS.push(event);
+        }
+    }
+}
+
+/// Stream of mouse events. Every stream receives all events from the moment
+/// it's created
+pub struct MouseStream {
+    subscription: Subscription<MouseEvent>,
+}
+
+impl MouseStream {
+    pub fn new() -> Self {
+        Self {
+            subscription: MOUSE_EVENTS.subscribe(),
+        }
+    }
+
+    /// Events this stream missed because it wasn't read fast enough
+    pub fn dropped(&self) -> u64 {
+        self.subscription.dropped()
+    }
+}
+
+impl Default for MouseStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stream for MouseStream {
+    type Item = MouseEvent;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.subscription.poll_next_unpin(cx)
+    }
+}
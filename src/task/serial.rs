@@ -0,0 +1,192 @@
+use alloc::string::String;
+use core::{
+    fmt::Write,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::Poll,
+};
+use futures_util::stream::{Stream, StreamExt};
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::{
+    input::{InputBuffer, Subscription},
+    line_editor::LineEditor,
+};
+use crate::{
+    interrupts::{self, IrqError},
+    serial::{terminal::TerminalDecoder, ComPort, SerialError, SerialPort, SERIAL_PORTS},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialInputError {
+    Serial(SerialError),
+    Irq(IrqError),
+}
+
+impl From<SerialError> for SerialInputError {
+    fn from(err: SerialError) -> Self {
+        SerialInputError::Serial(err)
+    }
+}
+
+impl From<IrqError> for SerialInputError {
+    fn from(err: IrqError) -> Self {
+        SerialInputError::Irq(err)
+    }
+}
+
+const NO_INPUT: InputBuffer<u8> = InputBuffer::new();
+/// Bytes received on each serial port, indexed by `ComPort::index`
+pub static RECEIVED: [InputBuffer<u8>; 4] = [NO_INPUT; 4];
+
+const DISABLED: AtomicBool = AtomicBool::new(false);
+/// Ports with receive interrupts enabled
+static RECEIVING: [AtomicBool; 4] = [DISABLED; 4];
+
+/// Start receiving on `port` through its IRQ line. Does nothing if it's
+/// already receiving
+pub fn init(port: ComPort) -> Result<(), SerialInputError> {
+    if RECEIVING[port.index()].load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    without_interrupts(|| {
+        let mut serial_port = SERIAL_PORTS[port.index()].lock();
+        serial_port.init()?;
+        // The line is shared with another port, which may have installed
+        // the handler already
+        let line_in_use = ComPort::ALL
+            .iter()
+            .any(|other| other.irq() == port.irq() && is_receiving(*other));
+        if !line_in_use {
+            interrupts::register_irq(port.irq(), serial_interrupt_handler)?;
+        }
+        RECEIVING[port.index()].store(true, Ordering::SeqCst);
+        serial_port.enable_receive_interrupt();
+        Ok(())
+    })
+}
+
+/// Whether `port` has receive interrupts enabled
+pub fn is_receiving(port: ComPort) -> bool {
+    RECEIVING[port.index()].load(Ordering::SeqCst)
+}
+
+/// Serial interrupt handler, for both ports on the line
+fn serial_interrupt_handler(irq: u8) {
+    for port in ComPort::ALL.iter().filter(|port| port.irq() == irq) {
+        if !is_receiving(*port) {
+            continue;
+        }
+        // Only the receive side is touched, so there's no need to wait for
+        // whoever is transmitting
+        let mut serial_port = unsafe { SerialPort::new(port.base()) };
+        while let Some(byte) = serial_port.try_receive() {
+            RECEIVED[port.index()].push(byte);
+        }
+    }
+}
+
+/// Stream of bytes received on a serial port. Every stream receives all
+/// bytes from the moment it's created
+pub struct SerialStream {
+    subscription: Subscription<u8>,
+}
+
+impl SerialStream {
+    pub fn new(port: ComPort) -> Self {
+        Self {
+            subscription: RECEIVED[port.index()].subscribe(),
+        }
+    }
+
+    /// Create a stream that can hold `capacity` bytes before dropping any
+    pub fn with_capacity(port: ComPort, capacity: usize) -> Self {
+        Self {
+            subscription: RECEIVED[port.index()].subscribe_with_capacity(capacity),
+        }
+    }
+
+    /// Bytes this stream missed because it wasn't read fast enough
+    pub fn dropped(&self) -> u64 {
+        self.subscription.dropped()
+    }
+}
+
+impl Stream for SerialStream {
+    type Item = u8;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.subscription.poll_next_unpin(cx)
+    }
+}
+
+/// Reads whole lines typed into a terminal on a serial port, echoing them
+/// back to it
+pub struct LineReader {
+    port: ComPort,
+    bytes: SerialStream,
+    decoder: TerminalDecoder,
+    editor: LineEditor,
+}
+
+impl LineReader {
+    pub fn new(port: ComPort) -> Self {
+        Self {
+            port,
+            bytes: SerialStream::new(port),
+            decoder: TerminalDecoder::new(),
+            editor: LineEditor::new(),
+        }
+    }
+
+    pub fn editor(&self) -> &LineEditor {
+        &self.editor
+    }
+
+    /// Write `s` to the terminal only, turning line feeds into CRLFs
+    pub fn write(&self, s: &str) {
+        without_interrupts(|| {
+            let mut serial_port = SERIAL_PORTS[self.port.index()].lock();
+            for (i, line) in s.split('\n').enumerate() {
+                if i > 0 {
+                    let _ = serial_port.write_str("\r\n");
+                }
+                let _ = serial_port.write_str(line);
+            }
+        });
+    }
+
+    /// Wait for a line to be entered. Supports the same editing keys as the
+    /// keyboard `LineReader`, as sent by VT100 compatible terminals
+    pub async fn read_line(&mut self) -> String {
+        let mut echo = String::new();
+        while let Some(byte) = self.bytes.next().await {
+            let (event, character) = match self.decoder.decode(byte) {
+                Some(press) => press,
+                None => continue,
+            };
+            let line = self.editor.handle(&event, character, &mut echo);
+            self.write(&echo);
+            echo.clear();
+            if let Some(line) = line {
+                return line;
+            }
+        }
+        self.editor.line()
+    }
+}
+
+/// Prompt for lines on `port` forever, passing each one to `handle` with
+/// surrounding whitespace trimmed. Output printed by `handle` reaches the
+/// terminal through the serial sink when `port` is COM1
+pub async fn run_console(port: ComPort, prompt: &str, mut handle: impl FnMut(&str)) {
+    let mut reader = LineReader::new(port);
+    loop {
+        reader.write(prompt);
+        let line = reader.read_line().await;
+        handle(line.trim());
+    }
+}
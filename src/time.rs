@@ -0,0 +1,132 @@
+use core::{
+    arch::x86_64::_rdtsc,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{hpet, interrupts, pit, rtc};
+
+/// Timer interrupts received since boot
+static TICKS: AtomicU64 = AtomicU64::new(0);
+/// Wall-clock time at tick 0, as a Unix timestamp
+static BOOT_TIME: AtomicU64 = AtomicU64::new(0);
+
+/// TSC increments per second. 0 until calibrated
+static TSC_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+/// TSC value at the time it was first calibrated, used as 0 for `now_ns`
+static TSC_START: AtomicU64 = AtomicU64::new(0);
+
+/// Read the wall-clock time from the RTC, start counting timer ticks and
+/// calibrate the TSC
+pub fn init() {
+    interrupts::register_irq(pit::TIMER_IRQ, timer_interrupt_handler)
+        .expect("Failed to register timer IRQ");
+    let boot_time = rtc::read().unix_timestamp() - uptime().as_secs();
+    BOOT_TIME.store(boot_time, Ordering::Relaxed);
+    calibrate_tsc();
+}
+
+fn rdtsc() -> u64 {
+    unsafe { _rdtsc() }
+}
+
+/// Measure the TSC frequency against the HPET, or the PIT if the HPET isn't
+/// available. Should be called again once the HPET has been initialized for
+/// better precision
+pub fn calibrate_tsc() {
+    const CALIBRATION_MS: u64 = 10;
+    let frequency = match hpet::get() {
+        Some(hpet) => {
+            let hpet_start = hpet.counter();
+            let tsc_start = rdtsc();
+            hpet.sleep_ns(CALIBRATION_MS * 1_000_000);
+            let tsc_end = rdtsc();
+            let hpet_elapsed = hpet.counter().wrapping_sub(hpet_start);
+            ((tsc_end - tsc_start) as u128 * hpet.frequency() as u128 / hpet_elapsed as u128) as u64
+        }
+        None => {
+            let tsc_start = rdtsc();
+            pit::sleep_ms(CALIBRATION_MS as u32);
+            (rdtsc() - tsc_start) * (1000 / CALIBRATION_MS)
+        }
+    };
+    TSC_FREQUENCY.store(frequency, Ordering::Relaxed);
+    let _ = TSC_START.compare_exchange(0, rdtsc(), Ordering::Relaxed, Ordering::Relaxed);
+}
+
+/// TSC frequency in Hz, or 0 if it hasn't been calibrated
+pub fn tsc_frequency() -> u64 {
+    TSC_FREQUENCY.load(Ordering::Relaxed)
+}
+
+/// Nanoseconds since the TSC was calibrated. Always 0 if it hasn't been
+pub fn now_ns() -> u64 {
+    let frequency = tsc_frequency();
+    if frequency == 0 {
+        return 0;
+    }
+    let elapsed = rdtsc() - TSC_START.load(Ordering::Relaxed);
+    (elapsed as u128 * 1_000_000_000 / frequency as u128) as u64
+}
+
+/// Run `f` and return how long it took, measured with the TSC
+pub fn measure<F: FnOnce()>(f: F) -> Duration {
+    let start = now_ns();
+    f();
+    Duration::from_nanos(now_ns() - start)
+}
+
+/// Timer interrupt handler
+fn timer_interrupt_handler(_irq: u8) {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of timer interrupts since boot. Ticks at `pit::TIMER_FREQUENCY`
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Time since boot, with the resolution of the timer interrupt
+pub fn uptime() -> Duration {
+    let ticks = ticks();
+    let frequency = pit::TIMER_FREQUENCY as u64;
+    Duration::from_secs(ticks / frequency)
+        + Duration::from_nanos((ticks % frequency) * 1_000_000_000 / frequency)
+}
+
+/// Current wall-clock time as a Unix timestamp
+pub fn now() -> u64 {
+    BOOT_TIME.load(Ordering::Relaxed) + uptime().as_secs()
+}
+
+// Tests
+
+#[test_case]
+fn ticks_advance() {
+    let before = ticks();
+    for _ in 0..5 {
+        x86_64::instructions::hlt();
+    }
+    assert!(ticks() > before);
+}
+
+#[test_case]
+fn now_matches_rtc() {
+    let rtc_time = rtc::read().unix_timestamp();
+    assert!(now().max(rtc_time) - now().min(rtc_time) <= 1);
+}
+
+#[test_case]
+fn tsc_is_calibrated() {
+    assert!(tsc_frequency() > 0);
+}
+
+#[test_case]
+fn now_ns_follows_pit() {
+    let elapsed = measure(|| pit::sleep_ms(20));
+    // Leave some slack, as timing under emulation is far from exact
+    assert!(elapsed >= Duration::from_millis(15), "{:?}", elapsed);
+    assert!(elapsed <= Duration::from_millis(30), "{:?}", elapsed);
+}
+
+// end of tests
@@ -1,19 +1,75 @@
 #![allow(dead_code)]
 
-use core::fmt::{self, Write};
+use core::{
+    fmt::{self, Write},
+    ptr::addr_of_mut,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use volatile::Volatile;
+use x86_64::instructions::port::Port;
+
+use self::ansi::{Action, Csi, Parser};
+use crate::{
+    console::{self, Backend, ConsoleSink},
+    keyboard::{Key, KeyEvent, KeyState},
+};
+
+pub(crate) mod ansi;
+mod cp437;
 
 const BUFFER_WIDTH: usize = 80;
 const BUFFER_HEIGHT: usize = 25;
+/// Lines kept after they scroll off the top of the screen
+pub const SCROLLBACK_LINES: usize = 200;
+/// Number of virtual terminals, switched between with Alt+F1 and onwards
+pub const TERMINAL_COUNT: usize = 4;
+/// Terminal that only shows kernel log messages, so they can be read without
+/// other output in between
+pub const LOG_TERMINAL: usize = 1;
+
+const VGA_BUFFER: usize = 0xb8000;
+
+// CRT controller registers
+const CRTC_INDEX: u16 = 0x3d4;
+const CRTC_DATA: u16 = 0x3d5;
+const CRTC_CURSOR_START: u8 = 0x0a;
+const CRTC_CURSOR_HIGH: u8 = 0x0e;
+const CRTC_CURSOR_LOW: u8 = 0x0f;
+const CURSOR_DISABLED: u8 = 1 << 5;
+
+const DEFAULT_COLOR: ColorCode = ColorCode::new(Color::Green, Color::Black);
+
+/// VGA colours in the order of the ANSI colour codes 0-7. Adding 8 gives the
+/// bright variant of each
+#[rustfmt::skip]
+const ANSI_COLORS: [Color; 8] = [
+    Color::Black, Color::Red, Color::Green, Color::Brown,
+    Color::Blue, Color::Magenta, Color::Cyan, Color::LightGray,
+];
+const BRIGHT: u8 = 0x08;
+
+// Too big to build on the stack while initialising `TERMINALS`
+static mut SCROLLBACKS: [Scrollback; TERMINAL_COUNT] = [Scrollback::new(); TERMINAL_COUNT];
+/// Contents of the terminals that aren't being displayed
+static mut SCREENS: [[Line; BUFFER_HEIGHT]; TERMINAL_COUNT] =
+    [[[ScreenChar::blank(DEFAULT_COLOR); BUFFER_WIDTH]; BUFFER_HEIGHT]; TERMINAL_COUNT];
+
+/// Index of the terminal shown on screen
+static ACTIVE_TERMINAL: AtomicUsize = AtomicUsize::new(0);
 
 lazy_static! {
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column: 0,
-        color: ColorCode::new(Color::Green, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    });
+    /// Virtual terminals. Only the active one writes to the VGA buffer, the
+    /// others write to memory until they're switched to
+    pub static ref TERMINALS: [Mutex<Writer>; TERMINAL_COUNT] = [
+        Mutex::new(Writer::new(0)),
+        Mutex::new(Writer::new(1)),
+        Mutex::new(Writer::new(2)),
+        Mutex::new(Writer::new(3)),
+    ];
+    /// The kernel console, which `print!` writes to in text mode
+    pub static ref WRITER: &'static Mutex<Writer> = &TERMINALS[0];
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,9 +97,25 @@ pub enum Color {
 #[repr(transparent)]
 struct ColorCode(u8);
 impl ColorCode {
-    fn new(foreground: Color, background: Color) -> ColorCode {
+    const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    fn foreground(self) -> u8 {
+        self.0 & 0x0f
+    }
+
+    fn background(self) -> u8 {
+        self.0 >> 4
+    }
+
+    fn with_foreground(self, foreground: u8) -> ColorCode {
+        ColorCode(self.0 & 0xf0 | foreground & 0x0f)
+    }
+
+    fn with_background(self, background: u8) -> ColorCode {
+        ColorCode(self.0 & 0x0f | (background & 0x0f) << 4)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -59,10 +131,69 @@ struct Buffer {
     chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+impl ScreenChar {
+    const fn blank(color: ColorCode) -> ScreenChar {
+        ScreenChar {
+            character: b' ',
+            color,
+        }
+    }
+}
+
+type Line = [ScreenChar; BUFFER_WIDTH];
+
+/// Ring buffer of the lines that scrolled off the top of the screen
+#[derive(Clone, Copy)]
+struct Scrollback {
+    lines: [Line; SCROLLBACK_LINES],
+    /// Index of the oldest line
+    start: usize,
+    len: usize,
+    /// The live screen, saved while older lines are being viewed
+    saved: [Line; BUFFER_HEIGHT],
+}
+
+impl Scrollback {
+    const fn new() -> Self {
+        let blank = ScreenChar::blank(DEFAULT_COLOR);
+        Scrollback {
+            lines: [[blank; BUFFER_WIDTH]; SCROLLBACK_LINES],
+            start: 0,
+            len: 0,
+            saved: [[blank; BUFFER_WIDTH]; BUFFER_HEIGHT],
+        }
+    }
+
+    fn push(&mut self, line: Line) {
+        if self.len < SCROLLBACK_LINES {
+            self.lines[(self.start + self.len) % SCROLLBACK_LINES] = line;
+            self.len += 1;
+        } else {
+            self.lines[self.start] = line;
+            self.start = (self.start + 1) % SCROLLBACK_LINES;
+        }
+    }
+
+    /// Line `index`, counting from the oldest one
+    fn get(&self, index: usize) -> &Line {
+        &self.lines[(self.start + index) % SCROLLBACK_LINES]
+    }
+}
+
 pub struct Writer {
+    row: usize,
     column: usize,
     color: ColorCode,
+    /// Set by SGR 1, makes the foreground colour bright
+    bold: bool,
+    parser: Parser,
     buffer: &'static mut Buffer,
+    scrollback: &'static mut Scrollback,
+    /// Number of lines the view is scrolled back by, 0 when showing the
+    /// live screen
+    view_offset: usize,
+    /// Whether this is the terminal on screen, which owns the hardware cursor
+    active: bool,
 }
 
 impl fmt::Write for Writer {
@@ -73,35 +204,181 @@ impl fmt::Write for Writer {
 }
 
 impl Writer {
+    /// Writer for terminal `index`. Only terminal 0 starts out on screen
+    fn new(index: usize) -> Writer {
+        let active = index == 0;
+        Writer {
+            row: BUFFER_HEIGHT - 1,
+            column: 0,
+            color: DEFAULT_COLOR,
+            bold: false,
+            parser: Parser::new(),
+            buffer: if active {
+                vga_buffer()
+            } else {
+                offscreen_buffer(index)
+            },
+            scrollback: unsafe { &mut (*addr_of_mut!(SCROLLBACKS))[index] },
+            view_offset: 0,
+            active,
+        }
+    }
+
     pub fn write_byte(&mut self, byte: u8) {
-        match byte {
-            b'\n' => self.new_line(),
-            byte => {
-                if self.column >= BUFFER_WIDTH {
-                    self.new_line();
+        self.put_byte(byte);
+        self.update_cursor();
+    }
+
+    /// Write a string, interpreting ANSI escape sequences and converting
+    /// everything else to code page 437
+    pub fn write_string(&mut self, string: &str) {
+        for c in string.chars() {
+            if !c.is_ascii() {
+                self.put_glyph(cp437::encode(c).unwrap_or(cp437::REPLACEMENT));
+                continue;
+            }
+            match self.parser.advance(c as u8) {
+                Action::Print(byte @ (0x20..=0x7e | b'\n' | 0x08)) => self.put_byte(byte),
+                Action::Print(_) => self.put_glyph(cp437::REPLACEMENT),
+                Action::Csi(csi) => self.execute_csi(&csi),
+                Action::None => {}
+            }
+        }
+        self.update_cursor();
+    }
+
+    fn execute_csi(&mut self, csi: &Csi) {
+        self.scroll_to_bottom();
+        let (row, column) = (self.row, self.column.min(BUFFER_WIDTH - 1));
+        let count = csi.param(0, 1) as usize;
+        match csi.command {
+            b'A' => self.row = row.saturating_sub(count),
+            b'B' => self.row = (row + count).min(BUFFER_HEIGHT - 1),
+            b'C' => self.column = (column + count).min(BUFFER_WIDTH - 1),
+            b'D' => self.column = column.saturating_sub(count),
+            b'G' => self.column = (count - 1).min(BUFFER_WIDTH - 1),
+            b'H' | b'f' => {
+                self.row = (count - 1).min(BUFFER_HEIGHT - 1);
+                self.column = (csi.param(1, 1) as usize - 1).min(BUFFER_WIDTH - 1);
+            }
+            b'J' => self.erase_screen(csi.param(0, 0)),
+            b'K' => self.erase_line(csi.param(0, 0)),
+            b'm' => self.select_graphic_rendition(csi.params()),
+            _ => {}
+        }
+    }
+
+    /// Erase from the cursor to the end of the screen (0), from the start of
+    /// the screen to the cursor (1) or the whole screen (2)
+    fn erase_screen(&mut self, mode: u16) {
+        let rows = match mode {
+            0 => self.row + 1..BUFFER_HEIGHT,
+            1 => 0..self.row,
+            2 => 0..BUFFER_HEIGHT,
+            _ => return,
+        };
+        for row in rows {
+            self.clear_row(row);
+        }
+        if mode != 2 {
+            self.erase_line(mode);
+        }
+    }
+
+    /// Erase from the cursor to the end of the line (0), from the start of the
+    /// line to the cursor (1) or the whole line (2)
+    fn erase_line(&mut self, mode: u16) {
+        let column = self.column.min(BUFFER_WIDTH - 1);
+        let columns = match mode {
+            0 => column..BUFFER_WIDTH,
+            1 => 0..column + 1,
+            2 => 0..BUFFER_WIDTH,
+            _ => return,
+        };
+        let empty = ScreenChar::blank(self.color);
+        for col in columns {
+            self.buffer.chars[self.row][col].write(empty);
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        let bright = |bold: bool| if bold { BRIGHT } else { 0 };
+        for &param in params {
+            let color = &mut self.color;
+            match param {
+                0 => {
+                    *color = DEFAULT_COLOR;
+                    self.bold = false;
+                }
+                1 => {
+                    self.bold = true;
+                    *color = color.with_foreground(color.foreground() | BRIGHT);
+                }
+                22 => {
+                    self.bold = false;
+                    *color = color.with_foreground(color.foreground() & !BRIGHT);
+                }
+                30..=37 => {
+                    let foreground = ANSI_COLORS[param as usize - 30] as u8 | bright(self.bold);
+                    *color = color.with_foreground(foreground);
+                }
+                39 => {
+                    let foreground = DEFAULT_COLOR.foreground() | bright(self.bold);
+                    *color = color.with_foreground(foreground);
                 }
-                let row = BUFFER_HEIGHT - 1;
-                let color = self.color;
-                let column = self.column;
-                self.buffer.chars[row][column].write(ScreenChar {
-                    character: byte,
-                    color,
-                });
-                self.column += 1;
+                40..=47 => *color = color.with_background(ANSI_COLORS[param as usize - 40] as u8),
+                49 => *color = color.with_background(DEFAULT_COLOR.background()),
+                90..=97 => {
+                    let foreground = ANSI_COLORS[param as usize - 90] as u8 | BRIGHT;
+                    *color = color.with_foreground(foreground);
+                }
+                100..=107 => {
+                    let background = ANSI_COLORS[param as usize - 100] as u8 | BRIGHT;
+                    *color = color.with_background(background);
+                }
+                _ => {}
             }
         }
     }
 
-    pub fn write_string(&mut self, string: &str) {
-        for byte in string.bytes() {
-            match byte {
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                _ => self.write_byte(0xfe),
+    fn put_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => {
+                self.scroll_to_bottom();
+                self.new_line();
             }
+            // Backspace only moves the cursor, the character is left in place
+            0x08 => self.column = self.column.saturating_sub(1),
+            byte => self.put_glyph(byte),
         }
     }
 
+    /// Write a code page 437 character, without treating any byte as control
+    /// character
+    fn put_glyph(&mut self, byte: u8) {
+        // New output always shows up on the live screen
+        self.scroll_to_bottom();
+        if self.column >= BUFFER_WIDTH {
+            self.new_line();
+        }
+        let row = self.row;
+        let color = self.color;
+        let column = self.column;
+        self.buffer.chars[row][column].write(ScreenChar {
+            character: byte,
+            color,
+        });
+        self.column += 1;
+    }
+
     fn new_line(&mut self) {
+        self.column = 0;
+        if self.row < BUFFER_HEIGHT - 1 {
+            self.row += 1;
+            return;
+        }
+        let top = self.read_row(0);
+        self.scrollback.push(top);
         for row in 1..BUFFER_HEIGHT {
             for col in 0..BUFFER_WIDTH {
                 let c = self.buffer.chars[row][col].read();
@@ -109,40 +386,278 @@ impl Writer {
             }
         }
         self.clear_row(BUFFER_HEIGHT - 1);
-        self.column = 0;
     }
 
     fn clear_row(&mut self, row: usize) {
-        let empty = ScreenChar {
-            character: b' ',
-            color: self.color,
-        };
+        let empty = ScreenChar::blank(self.color);
         for col in 0..BUFFER_WIDTH {
             self.buffer.chars[row][col].write(empty);
         }
     }
+
+    fn read_row(&self, row: usize) -> Line {
+        let mut line = [ScreenChar::blank(self.color); BUFFER_WIDTH];
+        for (col, c) in line.iter_mut().enumerate() {
+            *c = self.buffer.chars[row][col].read();
+        }
+        line
+    }
+
+    fn write_row(&mut self, row: usize, line: &Line) {
+        for (col, c) in line.iter().enumerate() {
+            self.buffer.chars[row][col].write(*c);
+        }
+    }
+
+    /// Current cursor position as `(row, column)`
+    pub fn position(&self) -> (usize, usize) {
+        (self.row, self.column)
+    }
+
+    /// Move the cursor, clamped to the screen
+    pub fn set_position(&mut self, row: usize, column: usize) {
+        self.row = row.min(BUFFER_HEIGHT - 1);
+        self.column = column.min(BUFFER_WIDTH - 1);
+        self.update_cursor();
+    }
+
+    /// Blank the screen and move the cursor to the top left corner. The
+    /// scrollback is kept
+    pub fn clear(&mut self) {
+        self.scroll_to_bottom();
+        for row in 0..BUFFER_HEIGHT {
+            self.clear_row(row);
+        }
+        self.set_position(0, 0);
+    }
+
+    /// Move the hardware cursor to the writer's position
+    fn update_cursor(&mut self) {
+        if !self.active {
+            return;
+        }
+        let position = (self.row * BUFFER_WIDTH + self.column.min(BUFFER_WIDTH - 1)) as u16;
+        write_crtc(CRTC_CURSOR_HIGH, (position >> 8) as u8);
+        write_crtc(CRTC_CURSOR_LOW, position as u8);
+    }
+
+    /// Number of lines the view is currently scrolled back by
+    pub fn view_offset(&self) -> usize {
+        self.view_offset
+    }
+
+    /// Show older lines from the scrollback, `lines` further up than now
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.set_view_offset(self.view_offset + lines);
+    }
+
+    /// Show newer lines again, back down to the live screen
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.set_view_offset(self.view_offset.saturating_sub(lines));
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(BUFFER_HEIGHT - 1);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(BUFFER_HEIGHT - 1);
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        if self.view_offset > 0 {
+            self.set_view_offset(0);
+        }
+    }
+
+    fn set_view_offset(&mut self, offset: usize) {
+        let offset = offset.min(self.scrollback.len);
+        if offset == self.view_offset {
+            return;
+        }
+        if self.view_offset == 0 {
+            for row in 0..BUFFER_HEIGHT {
+                self.scrollback.saved[row] = self.read_row(row);
+            }
+            if self.active {
+                set_cursor_visible(false);
+            }
+        }
+        self.view_offset = offset;
+
+        // Lines above the live screen come from the scrollback
+        let first = self.scrollback.len - offset;
+        for row in 0..BUFFER_HEIGHT {
+            let index = first + row;
+            let line = if index < self.scrollback.len {
+                *self.scrollback.get(index)
+            } else {
+                self.scrollback.saved[index - self.scrollback.len]
+            };
+            self.write_row(row, &line);
+        }
+        if offset == 0 && self.active {
+            set_cursor_visible(true);
+        }
+    }
+}
+
+fn vga_buffer() -> &'static mut Buffer {
+    unsafe { &mut *(VGA_BUFFER as *mut Buffer) }
+}
+
+/// Memory terminal `index` writes to while it isn't on screen. `Volatile` is
+/// transparent, so the plain lines have the same layout as `Buffer`
+fn offscreen_buffer(index: usize) -> &'static mut Buffer {
+    unsafe { &mut *(addr_of_mut!(SCREENS[index]) as *mut Buffer) }
+}
+
+fn copy_buffer(from: &Buffer, to: &mut Buffer) {
+    for row in 0..BUFFER_HEIGHT {
+        for col in 0..BUFFER_WIDTH {
+            to.chars[row][col].write(from.chars[row][col].read());
+        }
+    }
+}
+
+/// Index of the terminal currently on screen
+pub fn active_terminal() -> usize {
+    ACTIVE_TERMINAL.load(Ordering::Relaxed)
+}
+
+/// Put terminal `index` on screen, moving the current one off screen
+pub fn switch_terminal(index: usize) {
+    assert!(index < TERMINAL_COUNT, "No such terminal");
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let previous = active_terminal();
+        if previous == index {
+            return;
+        }
+        let mut old = TERMINALS[previous].lock();
+        let mut new = TERMINALS[index].lock();
+        old.scroll_to_bottom();
+
+        // Hand the VGA buffer over, keeping each terminal's contents
+        let vga = core::mem::replace(&mut old.buffer, offscreen_buffer(previous));
+        copy_buffer(vga, old.buffer);
+        copy_buffer(new.buffer, vga);
+        new.buffer = vga;
+
+        old.active = false;
+        new.active = true;
+        new.update_cursor();
+        set_cursor_visible(true);
+        ACTIVE_TERMINAL.store(index, Ordering::Relaxed);
+    });
+}
+
+/// Print to terminal `index`, whether it's on screen or not
+pub fn print_to(index: usize, args: fmt::Arguments) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        TERMINALS[index].lock().write_fmt(args).unwrap();
+    });
+}
+
+fn write_crtc(register: u8, value: u8) {
+    unsafe {
+        Port::<u8>::new(CRTC_INDEX).write(register);
+        Port::<u8>::new(CRTC_DATA).write(value);
+    }
 }
 
-#[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
+fn read_crtc(register: u8) -> u8 {
+    unsafe {
+        Port::<u8>::new(CRTC_INDEX).write(register);
+        Port::<u8>::new(CRTC_DATA).read()
+    }
 }
 
-#[macro_export]
-macro_rules! println {
-    () => ($crate::print!("\n"));
-    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+fn set_cursor_visible(visible: bool) {
+    let start = read_crtc(CRTC_CURSOR_START);
+    if visible {
+        write_crtc(CRTC_CURSOR_START, start & !CURSOR_DISABLED);
+    } else {
+        write_crtc(CRTC_CURSOR_START, start | CURSOR_DISABLED);
+    }
 }
 
-#[doc(hidden)]
-pub fn _print(args: fmt::Arguments) {
+/// Clear the screen
+pub fn clear_screen() {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        WRITER.lock().write_fmt(args).unwrap();
+        WRITER.lock().clear();
     });
 }
 
+/// Console sink for terminal 0
+pub struct TextSink;
+
+pub static TEXT_SINK: TextSink = TextSink;
+
+impl ConsoleSink for TextSink {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn write_str(&self, s: &str) {
+        WRITER.lock().write_string(s);
+    }
+
+    fn size(&self) -> Option<(usize, usize)> {
+        Some((BUFFER_HEIGHT, BUFFER_WIDTH))
+    }
+
+    fn position(&self) -> Option<(usize, usize)> {
+        x86_64::instructions::interrupts::without_interrupts(|| Some(WRITER.lock().position()))
+    }
+
+    fn set_position(&self, row: usize, column: usize) {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            WRITER.lock().set_position(row, column);
+        });
+    }
+
+    fn clear(&self) {
+        clear_screen();
+    }
+}
+
+/// Switch terminals on Alt+F1 to F4 and scroll the active one on
+/// Shift+PageUp/PageDown, as long as the text console is in use. Returns
+/// whether the key was used up
+pub fn handle_key(event: &KeyEvent) -> bool {
+    if event.state != KeyState::Down || console::backend() != Backend::Text {
+        return false;
+    }
+    if event.modifiers.alt {
+        let index = match event.key {
+            Key::F1 => 0,
+            Key::F2 => 1,
+            Key::F3 => 2,
+            Key::F4 => 3,
+            _ => return false,
+        };
+        switch_terminal(index);
+        return true;
+    }
+    if !event.modifiers.shift() {
+        return false;
+    }
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut terminal = TERMINALS[active_terminal()].lock();
+        match event.key {
+            Key::PageUp => terminal.page_up(),
+            Key::PageDown => terminal.page_down(),
+            _ => return false,
+        }
+        true
+    })
+}
+
 // Tests
 
+#[cfg(test)]
+use crate::println;
+
 #[test_case]
 fn can_println() {
     println!("Asserting print");
@@ -167,4 +682,220 @@ fn println_alters_buffer() {
     });
 }
 
+#[test_case]
+fn set_position_moves_output() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.set_position(5, 10);
+        writer.write_string("abc");
+        for (i, c) in "abc".chars().enumerate() {
+            let bufchar = writer.buffer.chars[5][10 + i].read();
+            assert_eq!(char::from(bufchar.character), c);
+        }
+        assert_eq!(writer.position(), (5, 13));
+        let cursor =
+            (read_crtc(CRTC_CURSOR_HIGH) as usize) << 8 | read_crtc(CRTC_CURSOR_LOW) as usize;
+        assert_eq!(cursor, 5 * BUFFER_WIDTH + 13);
+        writer.set_position(BUFFER_HEIGHT - 1, 0);
+    });
+}
+
+#[test_case]
+fn scrolled_lines_can_be_viewed() {
+    let s = "This line scrolls into the scrollback";
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.write_byte(b'\n');
+        writer.write_string(s);
+        for _ in 0..BUFFER_HEIGHT {
+            writer.write_byte(b'\n');
+        }
+        // The line is now just above the top of the screen
+        writer.scroll_up(1);
+        assert_eq!(writer.view_offset(), 1);
+        for (i, c) in s.chars().enumerate() {
+            let bufchar = writer.buffer.chars[0][i].read();
+            assert_eq!(char::from(bufchar.character), c);
+        }
+
+        writer.scroll_down(1);
+        assert_eq!(writer.view_offset(), 0);
+        let bufchar = writer.buffer.chars[0][0].read();
+        assert_eq!(bufchar.character, b' ');
+    });
+}
+
+#[test_case]
+fn writing_returns_to_live_screen() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        for _ in 0..BUFFER_HEIGHT {
+            writer.write_byte(b'\n');
+        }
+        writer.page_up();
+        assert!(writer.view_offset() > 0);
+        writer.write_string("x");
+        assert_eq!(writer.view_offset(), 0);
+        let bufchar = writer.buffer.chars[BUFFER_HEIGHT - 1][0].read();
+        assert_eq!(bufchar.character, b'x');
+        writer.write_byte(b'\n');
+    });
+}
+
+#[test_case]
+fn clear_blanks_screen() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.write_string("Some text");
+        writer.clear();
+        assert_eq!(writer.position(), (0, 0));
+        for row in 0..BUFFER_HEIGHT {
+            for col in 0..BUFFER_WIDTH {
+                assert_eq!(writer.buffer.chars[row][col].read().character, b' ');
+            }
+        }
+        writer.set_position(BUFFER_HEIGHT - 1, 0);
+    });
+}
+
+#[test_case]
+fn println_maps_unicode() {
+    let s = "┌─┐│└┘✓ ñ░€";
+    let expected = [
+        0xda, 0xc4, 0xbf, 0xb3, 0xc0, 0xd9, 0xfb, b' ', 0xa4, 0xb0, 0xfe,
+    ];
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        println!("\n{}", s);
+        for (i, byte) in expected.iter().enumerate() {
+            let bufchar = &WRITER.lock().buffer.chars[BUFFER_HEIGHT - 2][i].read();
+            assert_eq!(bufchar.character, *byte);
+        }
+    });
+}
+
+#[test_case]
+fn terminals_are_independent() {
+    let s = "Written to a terminal off screen";
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        WRITER.lock().write_string("\nOn the kernel console");
+    });
+    print_to(2, format_args!("\n{}", s));
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        assert!(!TERMINALS[2].lock().active);
+        let shown = WRITER.lock().buffer.chars[BUFFER_HEIGHT - 1][0].read();
+        assert_eq!(shown.character, b'O');
+    });
+
+    switch_terminal(2);
+    assert_eq!(active_terminal(), 2);
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let terminal = TERMINALS[2].lock();
+        assert!(terminal.active);
+        for (i, c) in s.chars().enumerate() {
+            let bufchar = terminal.buffer.chars[BUFFER_HEIGHT - 1][i].read();
+            assert_eq!(char::from(bufchar.character), c);
+        }
+    });
+
+    switch_terminal(0);
+    assert_eq!(active_terminal(), 0);
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        assert!(writer.active);
+        let shown = writer.buffer.chars[BUFFER_HEIGHT - 1][0].read();
+        assert_eq!(shown.character, b'O');
+        writer.write_string("\n");
+    });
+}
+
+#[test_case]
+fn log_has_its_own_terminal() {
+    let message = "Only on the log terminal";
+    log::warn!("{}", message);
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let terminal = TERMINALS[LOG_TERMINAL].lock();
+        let row = &terminal.buffer.chars[BUFFER_HEIGHT - 2];
+        let mut line = [0; BUFFER_WIDTH];
+        for (c, screen_char) in line.iter_mut().zip(row.iter()) {
+            *c = screen_char.read().character;
+        }
+        let line = core::str::from_utf8(&line).unwrap().trim_end();
+        assert!(line.ends_with(message), "{:?}", line);
+    });
+}
+
+/// Reset the writer's colours and move it back to the bottom row, where the
+/// other tests expect it
+#[cfg(test)]
+fn reset_writer(writer: &mut Writer) {
+    writer.write_string("\x1b[0m");
+    writer.set_position(BUFFER_HEIGHT - 1, 0);
+}
+
+#[test_case]
+fn sgr_sets_colors() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.set_position(3, 0);
+        writer.write_string("\x1b[31mr\x1b[01;34mb\x1b[0;97;44mw\x1b[0md");
+        let colors = [
+            ColorCode::new(Color::Red, Color::Black),
+            ColorCode::new(Color::LightBlue, Color::Black),
+            ColorCode::new(Color::White, Color::Blue),
+            DEFAULT_COLOR,
+        ];
+        for (i, (c, color)) in "rbwd".chars().zip(colors.iter()).enumerate() {
+            let bufchar = writer.buffer.chars[3][i].read();
+            assert_eq!(char::from(bufchar.character), c);
+            assert_eq!(bufchar.color, *color);
+        }
+        reset_writer(&mut writer);
+    });
+}
+
+#[test_case]
+fn csi_moves_cursor() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.write_string("\x1b[3;5H");
+        assert_eq!(writer.position(), (2, 4));
+        writer.write_string("\x1b[2B\x1b[3C");
+        assert_eq!(writer.position(), (4, 7));
+        writer.write_string("\x1b[A\x1b[10D");
+        assert_eq!(writer.position(), (3, 0));
+        writer.write_string("\x1b[20G\x1b[H");
+        assert_eq!(writer.position(), (0, 0));
+        writer.write_string("\x1b[99;99H");
+        assert_eq!(writer.position(), (BUFFER_HEIGHT - 1, BUFFER_WIDTH - 1));
+        reset_writer(&mut writer);
+    });
+}
+
+#[test_case]
+fn csi_erases_line() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.set_position(6, 0);
+        writer.write_string("abcdef\x1b[3D\x1b[K");
+        for (i, c) in b"abc   ".iter().enumerate() {
+            assert_eq!(writer.buffer.chars[6][i].read().character, *c);
+        }
+        writer.write_string("\x1b[2K");
+        assert_eq!(writer.buffer.chars[6][0].read().character, b' ');
+        reset_writer(&mut writer);
+    });
+}
+
+#[test_case]
+fn unknown_sequences_are_swallowed() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        writer.set_position(7, 0);
+        writer.write_string("\x1b[?25l\x1b[5zok");
+        assert_eq!(writer.buffer.chars[7][0].read().character, b'o');
+        assert_eq!(writer.position(), (7, 2));
+        reset_writer(&mut writer);
+    });
+}
+
 // end of tests
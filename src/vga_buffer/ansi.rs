@@ -0,0 +1,186 @@
+const ESCAPE: u8 = 0x1b;
+
+/// Parameters kept per CSI sequence, any further ones are ignored
+pub const MAX_PARAMS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Byte to be printed as is
+    Print(u8),
+    /// Complete CSI (`ESC [`) sequence to be executed
+    Csi(Csi),
+    /// Byte was swallowed as part of an escape sequence
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csi {
+    params: [u16; MAX_PARAMS],
+    count: usize,
+    /// Final byte, selecting what the sequence does
+    pub command: u8,
+}
+
+impl Csi {
+    const fn new() -> Self {
+        Csi {
+            params: [0; MAX_PARAMS],
+            count: 1,
+            command: 0,
+        }
+    }
+
+    /// Parameters of the sequence. Omitted ones are 0, and there is always at
+    /// least one
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.count.min(MAX_PARAMS)]
+    }
+
+    /// Parameter `index`, or `default` if it was omitted or 0
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+/// Splits a byte stream into printable bytes and ANSI escape sequences. Only
+/// CSI sequences are passed on, other escape sequences are dropped
+pub struct Parser {
+    state: State,
+    csi: Csi,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            csi: Csi::new(),
+        }
+    }
+
+    pub fn advance(&mut self, byte: u8) -> Action {
+        match self.state {
+            State::Ground if byte == ESCAPE => {
+                self.state = State::Escape;
+                Action::None
+            }
+            State::Ground => Action::Print(byte),
+            State::Escape if byte == b'[' => {
+                self.state = State::Csi;
+                self.csi = Csi::new();
+                Action::None
+            }
+            State::Escape => {
+                self.state = State::Ground;
+                Action::None
+            }
+            State::Csi => self.advance_csi(byte),
+        }
+    }
+
+    fn advance_csi(&mut self, byte: u8) -> Action {
+        let csi = &mut self.csi;
+        match byte {
+            b'0'..=b'9' => {
+                if let Some(param) = csi.params.get_mut(csi.count - 1) {
+                    *param = param
+                        .saturating_mul(10)
+                        .saturating_add((byte - b'0') as u16);
+                }
+                Action::None
+            }
+            b';' => {
+                // Going one past the end makes the digits of any further
+                // parameters get dropped
+                if csi.count <= MAX_PARAMS {
+                    csi.count += 1;
+                }
+                Action::None
+            }
+            // Private markers and intermediate bytes aren't used by anything
+            // we understand
+            0x20..=0x2f | 0x3c..=0x3f => Action::None,
+            0x40..=0x7e => {
+                self.state = State::Ground;
+                csi.command = byte;
+                Action::Csi(*csi)
+            }
+            // Anything else aborts the sequence
+            _ => {
+                self.state = State::Ground;
+                Action::None
+            }
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+fn parse_all(bytes: &[u8]) -> Option<Csi> {
+    let mut parser = Parser::new();
+    let mut result = None;
+    for byte in bytes {
+        if let Action::Csi(csi) = parser.advance(*byte) {
+            result = Some(csi);
+        }
+    }
+    result
+}
+
+#[test_case]
+fn plain_bytes_are_printed() {
+    let mut parser = Parser::new();
+    assert_eq!(parser.advance(b'a'), Action::Print(b'a'));
+    assert_eq!(parser.advance(b'\n'), Action::Print(b'\n'));
+}
+
+#[test_case]
+fn csi_params_are_parsed() {
+    let csi = parse_all(b"\x1b[01;34m").unwrap();
+    assert_eq!(csi.command, b'm');
+    assert_eq!(csi.params(), [1, 34]);
+
+    let csi = parse_all(b"\x1b[m").unwrap();
+    assert_eq!(csi.params(), [0]);
+    assert_eq!(csi.param(0, 1), 1);
+
+    let csi = parse_all(b"\x1b[;7H").unwrap();
+    assert_eq!(csi.params(), [0, 7]);
+    assert_eq!(csi.param(0, 1), 1);
+    assert_eq!(csi.param(1, 1), 7);
+    assert_eq!(csi.param(2, 1), 1);
+}
+
+#[test_case]
+fn extra_params_are_ignored() {
+    let csi = parse_all(b"\x1b[1;2;3;4;5;6;7;8;9;10m").unwrap();
+    assert_eq!(csi.params(), [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test_case]
+fn other_escapes_are_dropped() {
+    let mut parser = Parser::new();
+    assert_eq!(parser.advance(0x1b), Action::None);
+    assert_eq!(parser.advance(b'c'), Action::None);
+    assert_eq!(parser.advance(b'x'), Action::Print(b'x'));
+    // Private CSI sequences are still parsed, so they don't leave garbage
+    assert_eq!(parse_all(b"\x1b[?25l").unwrap().command, b'l');
+}
+
+// end of tests
@@ -0,0 +1,83 @@
+/// Glyph shown for characters code page 437 has nothing for
+pub const REPLACEMENT: u8 = 0xfe;
+
+/// Glyphs of the control characters 0x01-0x1f
+const LOW: &str = concat!("☺☻♥♦♣♠•◘○◙♂♀♪♫☼", "►◄↕‼¶§▬↨↑↓→←∟↔▲▼");
+
+/// Characters 0x80-0xff
+const HIGH: &str = concat!(
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜ¢£¥₧ƒ",
+    "áíóúñÑªº¿⌐¬½¼¡«»",
+    "░▒▓│┤╡╢╖╕╣║╗╝╜╛┐",
+    "└┴┬├─┼╞╟╚╔╩╦╠═╬╧",
+    "╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀",
+    "αßΓπΣσµτΦΘΩδ∞φε∩",
+    "≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}",
+);
+
+/// Characters without a glyph of their own, but with one that's close enough
+#[rustfmt::skip]
+const ALIASES: [(char, u8); 14] = [
+    ('✓', 0xfb), ('✔', 0xfb), ('✘', b'x'), ('✗', b'x'),
+    ('β', 0xe1), ('μ', 0xe6), ('∑', 0xe4), ('∈', 0xee),
+    ('‘', b'\''), ('’', b'\''), ('“', b'"'), ('”', b'"'),
+    ('⌂', 0x7f), ('–', b'-'),
+];
+
+/// Code page 437 byte displaying `c`, if there is one. Printable ASCII maps
+/// to itself
+pub fn encode(c: char) -> Option<u8> {
+    if (' '..='~').contains(&c) {
+        return Some(c as u8);
+    }
+    if let Some(index) = LOW.chars().position(|glyph| glyph == c) {
+        return Some(index as u8 + 0x01);
+    }
+    if let Some(index) = HIGH.chars().position(|glyph| glyph == c) {
+        return Some(index as u8 + 0x80);
+    }
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == c)
+        .map(|(_, byte)| *byte)
+}
+
+// Tests
+
+#[test_case]
+fn tables_are_complete() {
+    assert_eq!(LOW.chars().count(), 0x1f);
+    assert_eq!(HIGH.chars().count(), 0x80);
+}
+
+#[test_case]
+fn characters_are_encoded() {
+    assert_eq!(encode('A'), Some(b'A'));
+    assert_eq!(encode('~'), Some(b'~'));
+    assert_eq!(encode('☺'), Some(0x01));
+    assert_eq!(encode('▼'), Some(0x1f));
+    assert_eq!(encode('Ç'), Some(0x80));
+    assert_eq!(encode('─'), Some(0xc4));
+    assert_eq!(encode('┌'), Some(0xda));
+    assert_eq!(encode('█'), Some(0xdb));
+    assert_eq!(encode('π'), Some(0xe3));
+    assert_eq!(encode('√'), Some(0xfb));
+    assert_eq!(encode('\u{a0}'), Some(0xff));
+}
+
+#[test_case]
+fn aliases_are_encoded() {
+    assert_eq!(encode('✓'), Some(0xfb));
+    assert_eq!(encode('✘'), Some(b'x'));
+    assert_eq!(encode('μ'), encode('µ'));
+}
+
+#[test_case]
+fn unmappable_characters_are_rejected() {
+    assert_eq!(encode('\n'), None);
+    assert_eq!(encode('€'), None);
+    assert_eq!(encode('日'), None);
+}
+
+// end of tests
@@ -0,0 +1,123 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![feature(alloc_error_handler)]
+#![test_runner(blight_os::test_runner)]
+#![reexport_test_harness_main = "test_runner_entry"]
+
+use core::panic::PanicInfo;
+
+use blight_os::{
+    console::{self, Backend},
+    framebuffer::{self, font::Font, Rgb, FRAMEBUFFER},
+    graphics::Canvas,
+    memory::BootInfoFrameAllocator,
+    print, println,
+};
+use bootloader::{entry_point, BootInfo};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 480;
+const BLACK: Rgb = Rgb::new(0, 0, 0);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    blight_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { blight_os::memory::init(physical_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    framebuffer::init(
+        WIDTH as u16,
+        HEIGHT as u16,
+        &mut mapper,
+        &mut frame_allocator,
+    )
+    .expect("Failed to set up framebuffer");
+    assert!(console::select(Backend::Framebuffer));
+
+    test_runner_entry();
+    blight_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blight_os::test_panic(info)
+}
+
+/// Whether the cell at `row`, `column` shows `c` in `color`
+fn cell_shows(row: usize, column: usize, c: char, color: Rgb) -> bool {
+    let font = Font::default_font();
+    let framebuffer = FRAMEBUFFER.get().unwrap().lock();
+    let (x, y) = (column * font.width(), row * font.height());
+    // The cursor may be drawn over the bottom lines
+    font.glyph(c)
+        .iter()
+        .take(font.height() - 2)
+        .enumerate()
+        .all(|(line, bits)| {
+            (0..font.width()).all(|i| {
+                let expected = if bits & (0x80 >> i) != 0 {
+                    color
+                } else {
+                    BLACK
+                };
+                framebuffer.pixel((x + i) as i32, (y + line) as i32) == Some(expected)
+            })
+        })
+}
+
+#[test_case]
+fn mode_is_set() {
+    let framebuffer = FRAMEBUFFER.get().unwrap().lock();
+    assert_eq!((framebuffer.width(), framebuffer.height()), (WIDTH, HEIGHT));
+}
+
+#[test_case]
+fn console_fills_the_screen() {
+    assert_eq!(console::screen().size(), Some((30, 80)));
+}
+
+#[test_case]
+fn text_is_drawn() {
+    console::screen().set_position(2, 0);
+    print!("AB╬");
+    let green = Rgb::new(0x00, 0xaa, 0x00);
+    assert!(cell_shows(2, 0, 'A', green));
+    assert!(cell_shows(2, 1, 'B', green));
+    assert!(cell_shows(2, 2, '╬', green));
+    assert!(!cell_shows(2, 0, 'B', green));
+}
+
+#[test_case]
+fn colors_are_selected() {
+    console::screen().set_position(3, 0);
+    print!("\x1b[1;31mX\x1b[0m");
+    assert!(cell_shows(3, 0, 'X', Rgb::new(0xff, 0x55, 0x55)));
+}
+
+#[test_case]
+fn output_scrolls() {
+    console::screen().set_position(29, 0);
+    println!("Q");
+    assert!(cell_shows(28, 0, 'Q', Rgb::new(0x00, 0xaa, 0x00)));
+    assert_eq!(console::screen().position(), Some((29, 0)));
+}
+
+#[test_case]
+fn clear_blanks_the_screen() {
+    print!("Some text");
+    console::clear();
+    assert_eq!(console::screen().position(), Some((0, 0)));
+    let framebuffer = FRAMEBUFFER.get().unwrap().lock();
+    for y in (0..HEIGHT).step_by(3) {
+        for x in (0..WIDTH).step_by(3) {
+            // Except for the cursor in the top left cell
+            let is_cursor = x < 8 && (14..16).contains(&y);
+            assert!(is_cursor || framebuffer.pixel(x as i32, y as i32) == Some(BLACK));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![feature(alloc_error_handler)]
+#![test_runner(blight_os::test_runner)]
+#![reexport_test_harness_main = "test_runner_entry"]
+
+extern crate alloc;
+
+use core::panic::PanicInfo;
+
+use blight_os::{
+    framebuffer::Rgb,
+    graphics::{splash, Canvas, Rect, Surface},
+    memory::BootInfoFrameAllocator,
+};
+use bootloader::{entry_point, BootInfo};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    blight_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { blight_os::memory::init(physical_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    blight_os::allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("Heap allocation failed.");
+
+    test_runner_entry();
+    blight_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blight_os::test_panic(info)
+}
+
+const RED: Rgb = Rgb::new(0xff, 0, 0);
+const BLACK: Rgb = Rgb::new(0, 0, 0);
+
+/// Surface with nothing left to flush
+fn clean_surface(width: usize, height: usize) -> Surface {
+    let mut surface = Surface::new(width, height);
+    surface.flush(&mut Surface::new(1, 1), 0, 0);
+    surface
+}
+
+#[test_case]
+fn new_surface_is_dirty() {
+    let surface = Surface::new(20, 10);
+    assert_eq!(surface.dirty(), [Rect::new(0, 0, 20, 10)]);
+}
+
+#[test_case]
+fn drawing_marks_dirty_areas() {
+    let mut surface = clean_surface(20, 10);
+    assert!(surface.dirty().is_empty());
+    surface.draw_pixel(1, 1, RED);
+    surface.fill_rect(Rect::new(10, 5, 20, 20), RED);
+    assert_eq!(
+        surface.dirty(),
+        [Rect::new(1, 1, 1, 1), Rect::new(10, 5, 10, 5)]
+    );
+}
+
+#[test_case]
+fn overlapping_dirty_areas_are_merged() {
+    let mut surface = clean_surface(20, 10);
+    surface.fill_rect(Rect::new(0, 0, 4, 4), RED);
+    surface.fill_rect(Rect::new(8, 0, 4, 4), RED);
+    surface.draw_line((2, 2), (9, 2), RED);
+    assert_eq!(surface.dirty(), [Rect::new(0, 0, 12, 4)]);
+}
+
+#[test_case]
+fn dirty_areas_are_bounded() {
+    let mut surface = clean_surface(100, 10);
+    for x in (0..100).step_by(4) {
+        surface.draw_pixel(x, 0, RED);
+    }
+    assert!(surface.dirty().len() <= 16);
+    for x in (0..100).step_by(4) {
+        assert!(surface.dirty().iter().any(|area| area.contains(x, 0)));
+    }
+}
+
+#[test_case]
+fn flush_copies_only_dirty_areas() {
+    let mut surface = clean_surface(10, 10);
+    let mut target = clean_surface(30, 30);
+    surface.fill_rect(surface.bounds(), RED);
+    surface.flush(&mut Surface::new(1, 1), 0, 0);
+    surface.draw_pixel(3, 4, BLACK);
+    surface.flush(&mut target, 20, 25);
+
+    assert!(surface.dirty().is_empty());
+    assert_eq!(target.dirty(), [Rect::new(23, 29, 1, 1)]);
+    // Only the dirty pixel was copied, and nothing off the target
+    assert_eq!(target.pixel(23, 29), Some(BLACK));
+    assert_eq!(target.pixel(20, 25), Some(BLACK));
+}
+
+#[test_case]
+fn flush_is_clipped() {
+    let mut surface = Surface::new(10, 10);
+    surface.fill_rect(surface.bounds(), RED);
+    let mut target = clean_surface(8, 8);
+    surface.flush(&mut target, -5, 3);
+    assert_eq!(target.dirty(), [Rect::new(0, 3, 5, 5)]);
+    assert_eq!(target.pixel(4, 7), Some(RED));
+    assert_eq!(target.pixel(5, 7), Some(BLACK));
+    assert_eq!(target.pixel(4, 2), Some(BLACK));
+}
+
+#[test_case]
+fn splash_can_be_drawn() {
+    // Only the left edge, to keep it small enough for the test heap
+    let mut surface = clean_surface(64, splash::HEIGHT + 8);
+    splash::draw(&mut surface);
+    assert_eq!(
+        surface.dirty(),
+        [Rect::new(0, 0, 64, splash::HEIGHT as u32)]
+    );
+    assert_ne!(surface.pixel(4, 4), surface.pixel(8, 8));
+}
@@ -10,7 +10,7 @@ extern crate alloc;
 use core::panic::PanicInfo;
 
 use alloc::{boxed::Box, vec::Vec};
-use blight_os::{allocator::HEAP_SIZE, memory::BootInfoFrameAllocator, serial_println};
+use blight_os::{allocator::heap_size, memory::BootInfoFrameAllocator, serial_println};
 use bootloader::{entry_point, BootInfo};
 use x86_64::VirtAddr;
 
@@ -46,7 +46,7 @@ fn simple_allocation() {
 #[test_case]
 fn large_allocation() {
     let mut v = Vec::new();
-    let n = HEAP_SIZE / 32;
+    let n = heap_size() / 32;
     for i in 0..n {
         v.push(i);
     }
@@ -55,7 +55,7 @@ fn large_allocation() {
 
 #[test_case]
 fn reuse_memory() {
-    for i in 0..HEAP_SIZE {
+    for i in 0..heap_size() {
         let b = Box::new(i);
         assert_eq!(*b, i);
     }
@@ -64,7 +64,7 @@ fn reuse_memory() {
 #[test_case]
 fn reuse_memory_with_longlived() {
     let long_lived = Box::new(420);
-    for i in 0..HEAP_SIZE {
+    for i in 0..heap_size() {
         let b = Box::new(i);
         assert_eq!(*b, i);
     }
@@ -75,7 +75,7 @@ fn reuse_memory_with_longlived() {
 fn space_gets_released() {
     assert_eq!(
         blight_os::allocator::ALLOCATOR.lock().get_free_space(),
-        HEAP_SIZE,
+        heap_size(),
         "Some previous allocations weren't released"
     );
 }
@@ -84,14 +84,14 @@ fn space_gets_released() {
 fn reuse_merge() {
     // split heap in 4 free regions
     {
-        let _v1 = alloc::vec![1 as u64; HEAP_SIZE / 32];
-        let _v2 = alloc::vec![1 as u64; HEAP_SIZE / 32];
-        let _v3 = alloc::vec![1 as u64; HEAP_SIZE / 32];
+        let _v1 = alloc::vec![1 as u64; heap_size() / 32];
+        let _v2 = alloc::vec![1 as u64; heap_size() / 32];
+        let _v3 = alloc::vec![1 as u64; heap_size() / 32];
     }
     // attempt to allocate half of the heap. only works if previously released
     // regions were merged
-    let require_merge = alloc::vec![1 as u64; HEAP_SIZE/16];
-    assert_eq!(require_merge.iter().sum::<u64>() as usize, HEAP_SIZE / 16);
+    let require_merge = alloc::vec![1 as u64; heap_size()/16];
+    assert_eq!(require_merge.iter().sum::<u64>() as usize, heap_size() / 16);
 }
 
 #[test_case]
@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![feature(alloc_error_handler)]
+#![test_runner(blight_os::test_runner)]
+#![reexport_test_harness_main = "test_runner_entry"]
+
+extern crate alloc;
+
+use core::panic::PanicInfo;
+
+use blight_os::{
+    memory::BootInfoFrameAllocator,
+    task::input::{InputBuffer, DEFAULT_CAPACITY},
+};
+use bootloader::{entry_point, BootInfo};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    blight_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { blight_os::memory::init(physical_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    blight_os::allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("Heap allocation failed.");
+
+    test_runner_entry();
+    blight_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blight_os::test_panic(info)
+}
+
+#[test_case]
+fn every_subscriber_gets_every_event() {
+    static EVENTS: InputBuffer<u32> = InputBuffer::new();
+    let first = EVENTS.subscribe();
+    EVENTS.push(1);
+    let second = EVENTS.subscribe();
+    EVENTS.push(2);
+    EVENTS.push(3);
+
+    assert_eq!(first.try_next(), Some(1));
+    assert_eq!(first.try_next(), Some(2));
+    assert_eq!(first.try_next(), Some(3));
+    assert_eq!(first.try_next(), None);
+    // Events from before subscribing aren't delivered
+    assert_eq!(second.try_next(), Some(2));
+    assert_eq!(second.try_next(), Some(3));
+    assert_eq!(second.try_next(), None);
+}
+
+#[test_case]
+fn overflow_is_counted() {
+    static EVENTS: InputBuffer<u32> = InputBuffer::new();
+    let small = EVENTS.subscribe_with_capacity(2);
+    let large = EVENTS.subscribe();
+    assert_eq!(small.capacity(), 2);
+    assert_eq!(large.capacity(), DEFAULT_CAPACITY);
+    for i in 0..5 {
+        EVENTS.push(i);
+    }
+    assert_eq!(small.dropped(), 3);
+    assert_eq!(large.dropped(), 0);
+    assert_eq!(EVENTS.dropped(), 3);
+    // The oldest events are kept
+    assert_eq!(small.try_next(), Some(0));
+    assert_eq!(small.try_next(), Some(1));
+    assert_eq!(small.try_next(), None);
+}
+
+#[test_case]
+fn dropping_unsubscribes() {
+    static EVENTS: InputBuffer<u32> = InputBuffer::new();
+    // Nothing to deliver to
+    EVENTS.push(0);
+    let first = EVENTS.subscribe();
+    {
+        let _second = EVENTS.subscribe();
+        assert_eq!(EVENTS.subscriber_count(), 2);
+    }
+    assert_eq!(EVENTS.subscriber_count(), 1);
+    drop(first);
+    assert_eq!(EVENTS.subscriber_count(), 0);
+    assert_eq!(EVENTS.dropped(), 0);
+}
+
+#[test_case]
+fn scancode_streams_can_coexist() {
+    use blight_os::task::keyboard::{ScancodeStream, SCANCODES};
+    let before = SCANCODES.subscriber_count();
+    let _first = ScancodeStream::new();
+    let _second = ScancodeStream::with_capacity(16);
+    assert_eq!(SCANCODES.subscriber_count(), before + 2);
+}
@@ -0,0 +1,195 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![feature(alloc_error_handler)]
+#![test_runner(blight_os::test_runner)]
+#![reexport_test_harness_main = "test_runner_entry"]
+
+extern crate alloc;
+
+use core::panic::PanicInfo;
+
+use alloc::string::String;
+use blight_os::{
+    keyboard::{Key, KeyEvent, KeyState, Modifiers},
+    memory::BootInfoFrameAllocator,
+    task::line_editor::{LineEditor, HISTORY_SIZE},
+};
+use bootloader::{entry_point, BootInfo};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    blight_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { blight_os::memory::init(physical_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    blight_os::allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("Heap allocation failed.");
+
+    test_runner_entry();
+    blight_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blight_os::test_panic(info)
+}
+
+/// Press `key`, which the layout translated to `character`. Returns the
+/// finished line, if any, and what had to be echoed
+fn press(editor: &mut LineEditor, key: Key, character: Option<char>) -> (Option<String>, String) {
+    let event = KeyEvent {
+        key,
+        state: KeyState::Down,
+        modifiers: Modifiers::default(),
+    };
+    let mut echo = String::new();
+    let line = editor.handle(&event, character, &mut echo);
+    (line, echo)
+}
+
+fn type_str(editor: &mut LineEditor, text: &str) {
+    for c in text.chars() {
+        // The key doesn't matter for printable characters
+        press(editor, Key::A, Some(c));
+    }
+}
+
+fn enter(editor: &mut LineEditor) -> Option<String> {
+    press(editor, Key::Enter, Some('\n')).0
+}
+
+#[test_case]
+fn typed_line_is_returned() {
+    let mut editor = LineEditor::new();
+    let (_, echo) = press(&mut editor, Key::H, Some('h'));
+    assert_eq!(echo, "h");
+    type_str(&mut editor, "ello");
+    assert_eq!(enter(&mut editor).as_deref(), Some("hello"));
+    assert_eq!(editor.line(), "");
+}
+
+#[test_case]
+fn releases_are_ignored() {
+    let mut editor = LineEditor::new();
+    let event = KeyEvent {
+        key: Key::A,
+        state: KeyState::Up,
+        modifiers: Modifiers::default(),
+    };
+    let mut echo = String::new();
+    assert_eq!(editor.handle(&event, None, &mut echo), None);
+    assert_eq!(echo, "");
+    assert_eq!(editor.line(), "");
+}
+
+#[test_case]
+fn backspace_deletes_before_cursor() {
+    let mut editor = LineEditor::new();
+    type_str(&mut editor, "abc");
+    let (_, echo) = press(&mut editor, Key::Backspace, Some('\x08'));
+    assert_eq!(echo, "\x08 \x08");
+    assert_eq!(editor.line(), "ab");
+
+    press(&mut editor, Key::Left, None);
+    let (_, echo) = press(&mut editor, Key::Backspace, Some('\x08'));
+    assert_eq!(echo, "\x08b \x08\x08");
+    assert_eq!(editor.line(), "b");
+    assert_eq!(editor.cursor(), 0);
+
+    // Nothing left before the cursor
+    let (_, echo) = press(&mut editor, Key::Backspace, Some('\x08'));
+    assert_eq!(echo, "");
+    assert_eq!(editor.line(), "b");
+}
+
+#[test_case]
+fn cursor_moves_and_inserts() {
+    let mut editor = LineEditor::new();
+    type_str(&mut editor, "ac");
+    let (_, echo) = press(&mut editor, Key::Left, None);
+    assert_eq!(echo, "\x08");
+    let (_, echo) = press(&mut editor, Key::B, Some('b'));
+    assert_eq!(echo, "bc\x08");
+    assert_eq!(editor.line(), "abc");
+    assert_eq!(editor.cursor(), 2);
+
+    let (_, echo) = press(&mut editor, Key::Right, None);
+    assert_eq!(echo, "c");
+    // Already at the end
+    let (_, echo) = press(&mut editor, Key::Right, None);
+    assert_eq!(echo, "");
+
+    press(&mut editor, Key::Home, None);
+    assert_eq!(editor.cursor(), 0);
+    let (_, echo) = press(&mut editor, Key::Delete, None);
+    assert_eq!(echo, "bc \x08\x08\x08");
+    assert_eq!(editor.line(), "bc");
+    press(&mut editor, Key::End, None);
+    assert_eq!(editor.cursor(), 2);
+}
+
+#[test_case]
+fn enter_in_middle_returns_whole_line() {
+    let mut editor = LineEditor::new();
+    type_str(&mut editor, "hello");
+    press(&mut editor, Key::Left, None);
+    press(&mut editor, Key::Left, None);
+    assert_eq!(enter(&mut editor).as_deref(), Some("hello"));
+}
+
+#[test_case]
+fn control_characters_are_not_inserted() {
+    let mut editor = LineEditor::new();
+    press(&mut editor, Key::Tab, Some('\t'));
+    press(&mut editor, Key::C, Some('\x03'));
+    assert_eq!(editor.line(), "");
+}
+
+#[test_case]
+fn history_is_recalled() {
+    let mut editor = LineEditor::new();
+    type_str(&mut editor, "first");
+    enter(&mut editor);
+    type_str(&mut editor, "second");
+    enter(&mut editor);
+    // Empty lines and repeats aren't remembered
+    enter(&mut editor);
+    type_str(&mut editor, "second");
+    enter(&mut editor);
+    assert_eq!(editor.history(), ["first", "second"]);
+
+    type_str(&mut editor, "new");
+    let (_, echo) = press(&mut editor, Key::Up, None);
+    assert_eq!(echo, "\x08\x08\x08second");
+    assert_eq!(editor.line(), "second");
+    let (_, echo) = press(&mut editor, Key::Up, None);
+    assert_eq!(echo, "\x08\x08\x08\x08\x08\x08first \x08");
+    // No older entries
+    press(&mut editor, Key::Up, None);
+    assert_eq!(editor.line(), "first");
+
+    press(&mut editor, Key::Down, None);
+    assert_eq!(editor.line(), "second");
+    // Back to the line that was being typed
+    press(&mut editor, Key::Down, None);
+    assert_eq!(editor.line(), "new");
+    assert_eq!(editor.cursor(), 3);
+    press(&mut editor, Key::Down, None);
+    assert_eq!(editor.line(), "new");
+}
+
+#[test_case]
+fn history_is_bounded() {
+    let mut editor = LineEditor::new();
+    for i in 0..HISTORY_SIZE + 5 {
+        type_str(&mut editor, &alloc::format!("line {}", i));
+        enter(&mut editor);
+    }
+    assert_eq!(editor.history().len(), HISTORY_SIZE);
+    assert_eq!(editor.history()[0], "line 5");
+}
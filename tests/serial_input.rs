@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![feature(alloc_error_handler)]
+#![test_runner(blight_os::test_runner)]
+#![reexport_test_harness_main = "test_runner_entry"]
+
+extern crate alloc;
+
+use core::panic::PanicInfo;
+
+use blight_os::{
+    memory::BootInfoFrameAllocator,
+    serial::{ComPort, SERIAL1},
+    task::serial::{self, LineReader, SerialStream},
+};
+use bootloader::{entry_point, BootInfo};
+use futures_util::{future::FutureExt, stream::StreamExt};
+use x86_64::{instructions::interrupts::without_interrupts, VirtAddr};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    blight_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { blight_os::memory::init(physical_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    blight_os::allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("Heap allocation failed.");
+
+    test_runner_entry();
+    blight_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blight_os::test_panic(info)
+}
+
+/// Send `bytes` to COM1 itself, and give the interrupts time to arrive
+fn loop_back(bytes: &[u8]) {
+    without_interrupts(|| {
+        let mut com1 = SERIAL1.lock();
+        com1.set_loopback(true);
+        for &byte in bytes {
+            com1.send(byte);
+        }
+    });
+    for _ in 0..3 {
+        x86_64::instructions::hlt();
+    }
+    without_interrupts(|| SERIAL1.lock().set_loopback(false));
+}
+
+#[test_case]
+fn com1_is_receiving() {
+    assert!(serial::is_receiving(ComPort::Com1));
+    assert!(!serial::is_receiving(ComPort::Com2));
+}
+
+#[test_case]
+fn received_bytes_are_streamed() {
+    let mut stream = SerialStream::new(ComPort::Com1);
+    loop_back(b"hi");
+    assert_eq!(stream.next().now_or_never(), Some(Some(b'h')));
+    assert_eq!(stream.next().now_or_never(), Some(Some(b'i')));
+    assert_eq!(stream.next().now_or_never(), None);
+}
+
+#[test_case]
+fn lines_are_edited() {
+    let mut reader = LineReader::new(ComPort::Com1);
+    loop_back(b"ab\x1b[Dc\r\n");
+    assert_eq!(reader.read_line().now_or_never().as_deref(), Some("acb"));
+    assert_eq!(reader.editor().history(), ["acb"]);
+}
+
+#[test_case]
+fn missing_ports_cant_receive() {
+    assert!(serial::init(ComPort::Com4).is_err());
+    assert!(!serial::is_receiving(ComPort::Com4));
+}
@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![feature(alloc_error_handler)]
+#![test_runner(blight_os::test_runner)]
+#![reexport_test_harness_main = "test_runner_entry"]
+
+extern crate alloc;
+
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc::string::String;
+use blight_os::{
+    allocator::HEAP_START,
+    interrupts,
+    memory::{self, BootInfoFrameAllocator},
+    shell::{self, Command, ShellError},
+};
+use bootloader::{entry_point, BootInfo};
+use x86_64::{PhysAddr, VirtAddr};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    blight_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { blight_os::memory::init(physical_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    blight_os::allocator::init_heap(&mut mapper, &mut frame_allocator)
+        .expect("Heap allocation failed.");
+    shell::init();
+
+    test_runner_entry();
+    blight_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    blight_os::test_panic(info)
+}
+
+static ARGS_SEEN: AtomicUsize = AtomicUsize::new(0);
+
+fn count_args(args: &[&str]) {
+    ARGS_SEEN.store(args.len(), Ordering::SeqCst);
+}
+
+const COUNT: Command = Command {
+    name: "count",
+    usage: "count <args>...",
+    help: "Count the arguments",
+    run: count_args,
+};
+
+#[test_case]
+fn builtins_are_registered() {
+    assert!(shell::find("help").is_some());
+    assert!(shell::find("pt").is_some());
+    // Registering them again is harmless
+    shell::init();
+    assert!(shell::find("help").is_some());
+}
+
+#[test_case]
+fn registered_commands_run() {
+    assert_eq!(shell::register(COUNT), Ok(()));
+    assert_eq!(shell::register(COUNT), Err(ShellError::AlreadyRegistered));
+    assert_eq!(shell::execute("  count a  b c "), Ok(()));
+    assert_eq!(ARGS_SEEN.load(Ordering::SeqCst), 3);
+}
+
+#[test_case]
+fn unknown_commands_fail() {
+    assert_eq!(
+        shell::execute("frobnicate"),
+        Err(ShellError::UnknownCommand)
+    );
+    assert_eq!(shell::execute("   "), Ok(()));
+}
+
+#[test_case]
+fn addresses_are_translated() {
+    let vga = PhysAddr::new(0xb8000);
+    assert_eq!(memory::translate(memory::phys_to_virt(vga)), Some(vga));
+    let heap = VirtAddr::new(HEAP_START as u64);
+    assert!(memory::translate(heap).is_some());
+    // Nothing is mapped just below the heap
+    assert_eq!(memory::translate(heap - 4096u64), None);
+}
+
+#[test_case]
+fn interrupt_table_can_be_written_anywhere() {
+    let mut table = String::new();
+    interrupts::write_interrupt_table(&mut table).unwrap();
+    let mut lines = table.lines();
+    assert!(lines.next().unwrap().trim_start().starts_with("VECTOR"));
+    assert!(lines.any(|line| line.contains("timer")));
+}